@@ -3,29 +3,301 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
+use crate::fingerprint::Fingerprint;
 use crate::here;
-use anyhow::{bail, Context, Result};
-use rustdoc_types::{Crate, FORMAT_VERSION};
-use serde::Deserialize;
+use crate::rustdoc_json::{self, SUPPORTED_FORMAT_VERSIONS};
+use anyhow::{anyhow, bail, Context, Result};
+use cargo_metadata::{DependencyKind, Metadata, Node, Package, PackageId, Target};
+use rustdoc_types::Crate;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Output};
+use std::time::{Duration, SystemTime};
+use tracing::debug;
 
-#[derive(Deserialize)]
-struct CrateFormatVersion {
-    format_version: u32,
+/// Which Rust toolchain to run cargo and rustdoc with.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Toolchain {
+    /// Whichever toolchain `cargo` resolves to in the crate's directory
+    #[default]
+    Current,
+    /// A rustup toolchain with the given name (for example, `nightly-2023-05-31`)
+    Named(String),
+    /// The first installed rustup toolchain that produces a supported JSON format version
+    Auto,
+}
+
+impl std::str::FromStr for Toolchain {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "auto" => Self::Auto,
+            name => Self::Named(name.into()),
+        })
+    }
+}
+
+impl Toolchain {
+    /// Resolves [`Toolchain::Auto`] into a specific toolchain by probing the current toolchain,
+    /// and then every installed rustup toolchain, for the JSON format version that rustdoc
+    /// produces. Other toolchains are returned unchanged.
+    ///
+    /// The current toolchain is probed from `crate_path` so that `rust-toolchain.toml` overrides
+    /// are respected, and probe crates are documented in `probe_path`.
+    pub fn resolve(
+        self,
+        crate_path: &Path,
+        probe_path: &Path,
+        allow_bootstrap: bool,
+    ) -> Result<Toolchain> {
+        if self != Self::Auto {
+            return Ok(self);
+        }
+        let mut mismatches = Vec::new();
+        let candidates = std::iter::once(Self::Current)
+            .chain(installed_toolchains()?.into_iter().map(Self::Named));
+        for toolchain in candidates {
+            match probe_format_version(&toolchain, crate_path, probe_path, allow_bootstrap) {
+                Ok(format_version) if SUPPORTED_FORMAT_VERSIONS.contains(&format_version) => {
+                    eprintln!(
+                        "Using {toolchain} ({})",
+                        rustdoc_version(&toolchain, crate_path)?
+                    );
+                    return Ok(toolchain);
+                }
+                Ok(format_version) => {
+                    mismatches.push(format!("{toolchain}: format version {format_version}"))
+                }
+                Err(_) => mismatches.push(format!("{toolchain}: no JSON output")),
+            }
+        }
+        bail!(
+            "None of the installed toolchains produce a supported rustdoc JSON format version \
+            ({}):\n  {}\n\n\
+            Install a nightly toolchain that matches with `rustup toolchain install <name>`.",
+            rustdoc_json::supported_format_versions(),
+            mismatches.join("\n  ")
+        );
+    }
+
+    /// Returns a command that runs `program` with this toolchain.
+    fn command(&self, program: &str) -> Command {
+        match self {
+            Self::Named(name) => {
+                let mut command = Command::new("rustup");
+                command.arg("run").arg(name).arg(program);
+                command
+            }
+            // Use the same cargo that invoked this tool, if it was invoked as a cargo subcommand
+            Self::Current | Self::Auto if program == "cargo" => {
+                Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+            }
+            Self::Current | Self::Auto => Command::new(program),
+        }
+    }
+}
+
+impl std::fmt::Display for Toolchain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Current => write!(f, "the current toolchain"),
+            Self::Named(name) => write!(f, "toolchain `{name}`"),
+            Self::Auto => write!(f, "an automatically selected toolchain"),
+        }
+    }
+}
+
+/// Returns the names of the installed rustup toolchains.
+fn installed_toolchains() -> Result<Vec<String>> {
+    let output = Command::new("rustup")
+        .arg("toolchain")
+        .arg("list")
+        .output()
+        .context(here!("failed to run rustup"))?;
+    handle_failure("list rustup toolchains", &output)?;
+    Ok(parse_toolchain_list(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// Parses the output of `rustup toolchain list`, which has one toolchain per line
+/// followed by annotations such as `(default)`.
+fn parse_toolchain_list(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|name| *name != "no") // "no installed toolchains"
+        .map(String::from)
+        .collect()
+}
+
+/// Returns the output of `rustdoc --version` for the given toolchain.
+pub fn rustdoc_version(toolchain: &Toolchain, crate_path: &Path) -> Result<String> {
+    let output = toolchain
+        .command("rustdoc")
+        .current_dir(crate_path)
+        .arg("--version")
+        .output()
+        .context(here!("failed to run rustdoc"))?;
+    handle_failure("get the rustdoc version", &output)?;
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Documents a tiny crate with the given toolchain to find out which JSON format version it produces.
+pub fn probe_format_version(
+    toolchain: &Toolchain,
+    crate_path: &Path,
+    probe_path: &Path,
+    allow_bootstrap: bool,
+) -> Result<u32> {
+    let output_path = probe_path.join(match toolchain {
+        Toolchain::Named(name) => name.as_str(),
+        Toolchain::Current | Toolchain::Auto => "current",
+    });
+    fs::create_dir_all(&output_path).context(here!())?;
+    let source_path = probe_path.join("probe.rs");
+    fs::write(&source_path, "pub struct Probe;\n").context(here!())?;
+
+    let mut command = toolchain.command("rustdoc");
+    command
+        .current_dir(crate_path)
+        .arg(&source_path)
+        .arg("--crate-type")
+        .arg("lib")
+        .arg("--crate-name")
+        .arg("probe")
+        .arg("-Z")
+        .arg("unstable-options")
+        .arg("--output-format")
+        .arg("json")
+        .arg("-o")
+        .arg(&output_path);
+    if allow_bootstrap {
+        command.env("RUSTC_BOOTSTRAP", "1");
+    }
+    let output = command.output().context(here!("failed to run rustdoc"))?;
+    handle_failure("probe the rustdoc JSON format version", &output)?;
+
+    let json = fs::File::open(output_path.join("probe.json")).context(here!())?;
+    rustdoc_json::format_version(std::io::BufReader::new(json))
+}
+
+/// Which of a package's Cargo targets to document.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum CargoTarget {
+    /// The library target (equivalent to `cargo rustdoc --lib`)
+    #[default]
+    Lib,
+    /// The binary target with the given name (equivalent to `cargo rustdoc --bin <name>`)
+    Bin(String),
+    /// The example target with the given name (equivalent to `cargo rustdoc --example <name>`)
+    Example(String),
+}
+
+impl CargoTarget {
+    /// Finds this target in the given package.
+    pub fn find<'a>(&self, package: &'a Package) -> Result<&'a Target> {
+        match self {
+            Self::Lib => lib_target(package)
+                .ok_or_else(|| anyhow!("package `{}` has no library target", package.name)),
+            Self::Bin(name) => package
+                .targets
+                .iter()
+                .find(|target| target.is_bin() && &target.name == name)
+                .ok_or_else(|| anyhow!("package `{}` has no binary named `{name}`", package.name)),
+            Self::Example(name) => package
+                .targets
+                .iter()
+                .find(|target| target.is_example() && &target.name == name)
+                .ok_or_else(|| anyhow!("package `{}` has no example named `{name}`", package.name)),
+        }
+    }
+
+    fn add_args(&self, command: &mut Command) {
+        match self {
+            Self::Lib => command.arg("--lib"),
+            Self::Bin(name) => command.arg("--bin").arg(name),
+            Self::Example(name) => command.arg("--example").arg(name),
+        };
+    }
+}
+
+/// Options that are passed through to every cargo command that gets run.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CargoOptions {
+    /// Require `Cargo.lock` to be up to date (`--locked`)
+    pub locked: bool,
+    /// Require `Cargo.lock` and the cache to be up to date (`--frozen`)
+    pub frozen: bool,
+    /// Run without accessing the network (`--offline`)
+    pub offline: bool,
+    /// Configuration overrides, as `KEY=VALUE` pairs or paths to config files (`--config`)
+    pub config: Vec<String>,
+    /// Unstable flags (`-Z`)
+    pub unstable_flags: Vec<String>,
+}
+
+impl CargoOptions {
+    /// Returns the command line arguments for these options.
+    pub fn args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if self.locked {
+            args.push("--locked".into());
+        }
+        if self.frozen {
+            args.push("--frozen".into());
+        }
+        if self.offline {
+            args.push("--offline".into());
+        }
+        for config in &self.config {
+            args.push("--config".into());
+            args.push(config.clone());
+        }
+        for flag in &self.unstable_flags {
+            args.push("-Z".into());
+            args.push(flag.clone());
+        }
+        args
+    }
 }
 
 /// Runs the `cargo rustdoc` command required to produce Rustdoc's JSON output with a nightly compiler.
 pub struct CargoRustDocJson {
-    /// Name of the crate (as specified in the Cargo.toml file)
+    /// Crate name of the Cargo target being documented (for libraries, this is the
+    /// `[lib] name` from Cargo.toml if it is set, and the package name otherwise)
     crate_name: String,
     /// Path of the crate to examine
     crate_path: PathBuf,
-    /// Expected `target/` directory where the output will be
+    /// The workspace's `target/` directory. Cargo is told to use a dedicated `check-external-types`
+    /// subdirectory of it so that the output never clobbers (or races with) `cargo doc`.
     target_path: PathBuf,
-    /// Features to enable
+    /// Features to enable (default features are only enabled if `default` is in this list)
     features: Vec<String>,
+    /// Target triple to document for, or `None` for the host
+    target: Option<String>,
+    /// Cargo target of the package to document
+    cargo_target: CargoTarget,
+    /// Options to pass through to cargo
+    cargo_options: CargoOptions,
+    /// Extra arguments to pass to rustdoc
+    rustdoc_args: Vec<String>,
+    /// Whether to set `RUSTC_BOOTSTRAP=1` so that a stable toolchain accepts unstable options
+    allow_bootstrap: bool,
+    /// Toolchain to run cargo with
+    toolchain: Toolchain,
+    /// Source files and directories to fingerprint so that previous output can be reused,
+    /// or `None` to always run rustdoc
+    cache_sources: Option<Vec<PathBuf>>,
+    /// Name to add to the output's file name, so that runs with different features don't
+    /// overwrite each other's output
+    output_name: Option<String>,
+    /// Subdirectory of the target directory for cargo to build in, so that runs at the same
+    /// time don't wait on each other's build directory lock
+    build_dir: Option<String>,
 }
 
 impl CargoRustDocJson {
@@ -40,60 +312,455 @@ impl CargoRustDocJson {
             crate_path: crate_path.into(),
             target_path: target_path.into(),
             features,
+            target: None,
+            cargo_target: CargoTarget::Lib,
+            cargo_options: CargoOptions::default(),
+            rustdoc_args: Vec::new(),
+            allow_bootstrap: false,
+            toolchain: Toolchain::Current,
+            cache_sources: None,
+            output_name: None,
+            build_dir: None,
         }
     }
 
+    /// Sets a name to add to the output's file name (for example, to tell feature sets apart).
+    /// The output is moved to the new name after rustdoc writes it, so that it can't be
+    /// overwritten by a run with the same target directory, and can be reused by the next run
+    /// with the same name.
+    pub fn with_output_name(mut self, output_name: Option<String>) -> Self {
+        self.output_name = output_name;
+        self
+    }
+
+    /// Sets a subdirectory of the target directory for cargo to build in. Cargo locks its build
+    /// directory while it runs, so runs that happen at the same time need different ones to
+    /// overlap. The output is moved to the usual place after rustdoc writes it, so it can still
+    /// be reused by a later run with a different build directory.
+    pub fn with_build_dir(mut self, build_dir: Option<String>) -> Self {
+        self.build_dir = build_dir;
+        self
+    }
+
+    /// Enables reusing the JSON output from a previous run when none of its inputs have
+    /// changed. The given files and directories (usually from [`local_sources`]) are hashed
+    /// along with the toolchain version and every option that gets passed to cargo.
+    pub fn with_cache_sources(mut self, cache_sources: Option<Vec<PathBuf>>) -> Self {
+        self.cache_sources = cache_sources;
+        self
+    }
+
+    /// Sets the toolchain to run cargo with. [`Toolchain::Auto`] must be resolved with
+    /// [`Toolchain::resolve`] first, otherwise the current toolchain is used.
+    pub fn with_toolchain(mut self, toolchain: Toolchain) -> Self {
+        self.toolchain = toolchain;
+        self
+    }
+
+    /// Sets whether to set `RUSTC_BOOTSTRAP=1` for cargo, which makes a stable toolchain accept
+    /// the unstable options needed for rustdoc's JSON output. Defaults to `false`.
+    pub fn with_allow_bootstrap(mut self, allow_bootstrap: bool) -> Self {
+        self.allow_bootstrap = allow_bootstrap;
+        self
+    }
+
+    /// Sets options to pass through to cargo.
+    pub fn with_cargo_options(mut self, cargo_options: CargoOptions) -> Self {
+        self.cargo_options = cargo_options;
+        self
+    }
+
+    /// Sets extra arguments to pass to rustdoc, such as `--cfg docsrs`.
+    pub fn with_rustdoc_args(mut self, rustdoc_args: Vec<String>) -> Self {
+        self.rustdoc_args = rustdoc_args;
+        self
+    }
+
+    /// Sets which of the package's Cargo targets to document. Defaults to the library.
+    pub fn with_cargo_target(mut self, cargo_target: CargoTarget) -> Self {
+        self.cargo_target = cargo_target;
+        self
+    }
+
+    /// Sets the target triple to document the crate for. Defaults to the host.
+    pub fn with_target(mut self, target: Option<String>) -> Self {
+        self.target = target;
+        self
+    }
+
     pub fn run(&self) -> Result<Crate> {
-        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let started = self.generate()?;
+        self.read_output(started)
+    }
+
+    /// Runs rustdoc, unless its output from a previous run can be reused. Returns when rustdoc
+    /// was started, or `None` if the output was reused.
+    fn generate(&self) -> Result<Option<SystemTime>> {
+        let fingerprint_path = self.output_path().with_extension("fingerprint");
+        let fingerprint = match self.fingerprint() {
+            Ok(fingerprint) => fingerprint,
+            Err(err) => {
+                debug!("not caching rustdoc output: {err:#}");
+                None
+            }
+        };
+        if let Some(fingerprint) = &fingerprint {
+            if fingerprint.matches_stored(&fingerprint_path, &self.output_path()) {
+                eprintln!("Reusing rustdoc JSON output since its inputs haven't changed.");
+                return Ok(None);
+            }
+        }
+        // Remove the stale fingerprint first so that it can't match if rustdoc fails
+        let _ = fs::remove_file(&fingerprint_path);
 
-        let mut command = Command::new(cargo);
+        let mut command = self.toolchain.command("cargo");
         command.current_dir(&self.crate_path).arg("rustdoc");
+        // A Cargo target must be selected explicitly since cargo refuses to pass
+        // extra arguments to rustdoc when a package has more than one target
+        self.cargo_target.add_args(&mut command);
+        // The given features are the complete set to enable (including `default` if it should
+        // be enabled), so default features must always be disabled. Otherwise, checking with
+        // an empty feature set would enable the default features.
+        command.arg("--no-default-features");
         if !self.features.is_empty() {
-            command.arg("--no-default-features").arg("--features");
-            command.arg(&self.features.join(","));
+            command.arg("--features").arg(self.features.join(","));
         }
+        if let Some(target) = &self.target {
+            command.arg("--target").arg(target);
+        }
+        // Always pass the target directory explicitly so that the output is read back from
+        // the same place regardless of `CARGO_TARGET_DIR` or cargo config overrides
+        command.arg("--target-dir").arg(self.build_target_path());
+        command.args(self.cargo_options.args());
         command
             .arg("--")
             .arg("--document-private-items")
             .arg("-Z")
             .arg("unstable-options")
             .arg("--output-format")
-            .arg("json");
+            .arg("json")
+            .args(&self.rustdoc_args);
+        if self.allow_bootstrap {
+            command.env("RUSTC_BOOTSTRAP", "1");
+        }
+        let started = SystemTime::now();
         let output = command
             .output()
             .context(here!("failed to run nightly rustdoc"))?;
-        handle_failure("rustdoc", &output)?;
+        let result = handle_failure("rustdoc", &output);
+        if let Some(target) = &self.target {
+            result.with_context(|| {
+                format!(
+                    "Failed to document for target `{target}`. If the standard library for it \
+                    isn't installed, install it with `rustup target add {target}`."
+                )
+            })?;
+        } else if !self.allow_bootstrap
+            && String::from_utf8_lossy(&output.stderr).contains("nightly")
+        {
+            result.context(
+                "Rustdoc JSON output requires a nightly toolchain. Either run with a nightly \
+                toolchain (for example, `cargo +nightly check-external-types` or \
+                `--toolchain auto`), or opt into using the current toolchain's unstable \
+                features with `--allow-bootstrap`.",
+            )?;
+        } else {
+            result?;
+        }
 
-        let output_file_name = self
-            .target_path
-            .canonicalize()
-            .context(here!("failed to canonicalize {:?}", self.target_path))?
-            .join(format!("doc/{}.json", self.crate_name.replace('-', "_")));
-
-        let json = fs::read_to_string(output_file_name).context(here!())?;
-        let format_version: CrateFormatVersion = serde_json::from_str(&json)
-            .context("Failed to find `format_version` in rustdoc JSON output.")
-            .context(here!())?;
-        if format_version.format_version != FORMAT_VERSION {
+        let rustdoc_output_path = self.output_path_in(self.build_target_path(), None);
+        if rustdoc_output_path != self.output_path() {
+            if let Some(parent) = self.output_path().parent() {
+                fs::create_dir_all(parent).context(here!("failed to create {parent:?}"))?;
+            }
+            fs::rename(&rustdoc_output_path, self.output_path())
+                .context(here!("failed to rename {rustdoc_output_path:?}"))?;
+        }
+        if let Some(mut fingerprint) = fingerprint {
+            fingerprint.add_output(&self.output_path())?;
+            fingerprint.write(&fingerprint_path)?;
+        }
+        Ok(Some(started))
+    }
+
+    /// Reads rustdoc's JSON output, and verifies that it's for the expected crate. If rustdoc
+    /// was run at `started`, this also verifies that the output was written by that run rather
+    /// than left over from an earlier one.
+    fn read_output(&self, started: Option<SystemTime>) -> Result<Crate> {
+        let output_file_name = self.output_file_name()?;
+        if let Some(started) = started {
+            let modified = fs::metadata(&output_file_name)
+                .and_then(|metadata| metadata.modified())
+                .context(here!("failed to read {output_file_name:?}"))?;
+            // Allow for file systems that only store modification times to the second
+            if modified + Duration::from_secs(2) < started {
+                bail!(
+                    "Rustdoc didn't write its JSON output to {output_file_name:?} (the file there \
+                    is from an earlier run). Make sure that rustdoc arguments such as `--out-dir` \
+                    aren't being passed in through `RUSTDOCFLAGS` or the cargo config."
+                );
+            }
+        }
+
+        let package = rustdoc_json::read_crate(&output_file_name).context(here!())?;
+        let root_name = package
+            .index
+            .get(&package.root)
+            .and_then(|root| root.name.as_deref());
+        if root_name != Some(self.crate_name.replace('-', "_").as_str()) {
             bail!(
-                "The version of rustdoc being used produces JSON format version {0}, but \
-                this tool requires format version {1}. This can happen if the locally \
-                installed version of rustdoc doesn't match the rustdoc JSON types from \
-                the `rustdoc-types` crate.\n\n\
-                If this occurs with the latest Rust nightly and the latest version of this \
-                tool, then this is a bug, and the tool needs to be upgraded to the latest \
-                format version.\n\n\
-                Otherwise, you'll need to determine a Rust nightly version that matches \
-                this tool's supported format version (or vice versa).",
-                format_version.format_version,
-                FORMAT_VERSION
+                "{output_file_name:?} documents crate `{}` instead of `{}`",
+                root_name.unwrap_or("<unknown>"),
+                self.crate_name
             );
         }
-        let package: Crate = serde_json::from_str(&json)
-            .context("Failed to parse rustdoc output.")
-            .context(here!())?;
         Ok(package)
     }
+
+    /// Returns the fingerprint of every input to rustdoc's output, or `None` if caching is
+    /// disabled.
+    fn fingerprint(&self) -> Result<Option<Fingerprint>> {
+        let Some(cache_sources) = &self.cache_sources else {
+            return Ok(None);
+        };
+        let mut fingerprint = Fingerprint::new();
+        fingerprint.add(
+            "rustdoc-version",
+            rustdoc_version(&self.toolchain, &self.crate_path)?,
+        );
+        fingerprint.add("crate-name", &self.crate_name);
+        fingerprint.add("cargo-target", format!("{:?}", self.cargo_target));
+        fingerprint.add("features", self.features.join(","));
+        fingerprint.add("target", self.target.clone().unwrap_or_default());
+        fingerprint.add("cargo-options", self.cargo_options.args().join(" "));
+        fingerprint.add("rustdoc-args", self.rustdoc_args.join(" "));
+        fingerprint.add("allow-bootstrap", self.allow_bootstrap.to_string());
+        for var in [
+            "RUSTFLAGS",
+            "RUSTDOCFLAGS",
+            "CARGO_ENCODED_RUSTFLAGS",
+            "CARGO_ENCODED_RUSTDOCFLAGS",
+            "CARGO_BUILD_RUSTFLAGS",
+            "CARGO_BUILD_RUSTDOCFLAGS",
+            "CARGO_BUILD_TARGET",
+            "RUSTC_BOOTSTRAP",
+        ] {
+            if let Ok(value) = std::env::var(var) {
+                fingerprint.add(format!("env {var}"), value);
+            }
+        }
+        for source in cache_sources {
+            fingerprint.add_path(source, &[self.target_path.clone()])?;
+        }
+        // Cargo config files can set rustdoc flags and the target, and they're hidden, so the
+        // sources above never include them
+        let crate_path = self
+            .crate_path
+            .canonicalize()
+            .unwrap_or_else(|_| self.crate_path.clone());
+        for config_path in cargo_config_paths(&crate_path) {
+            fingerprint.add_path(&config_path, &[])?;
+        }
+        Ok(Some(fingerprint))
+    }
+
+    /// Returns the path of the JSON file that rustdoc writes its output to.
+    fn output_file_name(&self) -> Result<PathBuf> {
+        let target_path = self.output_target_path();
+        let target_path = target_path
+            .canonicalize()
+            .context(here!("failed to canonicalize {target_path:?}"))?;
+        Ok(self.output_path_in(target_path, self.output_name.as_deref()))
+    }
+
+    /// Returns the path that rustdoc's JSON output will be written to, without requiring
+    /// the target directory to exist yet.
+    pub fn output_path(&self) -> PathBuf {
+        self.output_path_in(self.output_target_path(), self.output_name.as_deref())
+    }
+
+    /// Returns the target directory that the output is read from.
+    fn output_target_path(&self) -> PathBuf {
+        self.target_path.join("check-external-types")
+    }
+
+    /// Returns the target directory that cargo is told to use.
+    fn build_target_path(&self) -> PathBuf {
+        match &self.build_dir {
+            Some(build_dir) => self.output_target_path().join(build_dir),
+            None => self.output_target_path(),
+        }
+    }
+
+    fn output_path_in(&self, mut doc_path: PathBuf, output_name: Option<&str>) -> PathBuf {
+        if let Some(target) = &self.target {
+            doc_path.push(target);
+        }
+        doc_path.push("doc");
+        let crate_name = self.crate_name.replace('-', "_");
+        match output_name {
+            Some(output_name) => doc_path.join(format!("{crate_name}.{output_name}.json")),
+            None => doc_path.join(format!("{crate_name}.json")),
+        }
+    }
+}
+
+/// Returns the library target of a package, if it has one.
+pub fn lib_target(package: &Package) -> Option<&Target> {
+    package.targets.iter().find(|target| {
+        target.kind.iter().any(|kind| {
+            matches!(
+                kind.as_str(),
+                "lib" | "rlib" | "dylib" | "cdylib" | "staticlib" | "proc-macro"
+            )
+        })
+    })
+}
+
+/// A version of a crate in the normal dependency graph of a package.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CrateVersion {
+    pub package_name: String,
+    pub version: String,
+    /// Names that the root package imports this version as, if it's a direct dependency
+    pub extern_names: Vec<String>,
+    /// Names of the packages that the root package depends on this version through, from
+    /// the root package's dependency down to the package that depends on this version. This
+    /// is empty for direct dependencies.
+    pub via: Vec<String>,
+}
+
+impl fmt::Display for CrateVersion {
+    /// Formats the version like `http 0.2.9, via hyper`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.package_name, self.version)?;
+        if !self.via.is_empty() {
+            write!(f, ", via {}", self.via.join(" → "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the versions of every crate in the normal dependency graph of `root_package`,
+/// keyed by the crate name that rustdoc uses as the first segment of type paths, and ordered
+/// from oldest to newest.
+///
+/// Each version is reached through the shortest dependency path from the root package.
+pub fn dependency_versions(
+    metadata: &Metadata,
+    root_package: &Package,
+) -> Result<BTreeMap<String, Vec<CrateVersion>>> {
+    let resolve = metadata
+        .resolve
+        .as_ref()
+        .ok_or_else(|| anyhow!("Cargo metadata didn't have resolved nodes"))?;
+    let nodes: HashMap<&PackageId, &Node> =
+        resolve.nodes.iter().map(|node| (&node.id, node)).collect();
+    let node = |id: &PackageId| {
+        nodes
+            .get(id)
+            .copied()
+            .ok_or_else(|| anyhow!("Failed to find node for package {id}"))
+    };
+    let root_deps = &node(&root_package.id)?.deps;
+
+    let mut versions: BTreeMap<String, Vec<&Package>> = BTreeMap::new();
+    let mut via: HashMap<&PackageId, Vec<String>> = HashMap::new();
+    // Visiting the packages breadth first finds the shortest path to each of them
+    let mut queue = VecDeque::from([(&root_package.id, Vec::new())]);
+    let mut visited = HashSet::new();
+    while let Some((id, path)) = queue.pop_front() {
+        if !visited.insert(id) {
+            continue;
+        }
+        let is_root = id == &root_package.id;
+        for dep in &node(id)?.deps {
+            if dep
+                .dep_kinds
+                .iter()
+                .any(|info| info.kind == DependencyKind::Normal)
+            {
+                let mut dep_path = path.clone();
+                if !is_root {
+                    dep_path.push(metadata[id].name.clone());
+                }
+                queue.push_back((&dep.pkg, dep_path));
+            }
+        }
+        if !is_root {
+            let package = &metadata[id];
+            if let Some(target) = lib_target(package) {
+                versions
+                    .entry(target.name.replace('-', "_"))
+                    .or_default()
+                    .push(package);
+                via.insert(id, path);
+            }
+        }
+    }
+    Ok(versions
+        .into_iter()
+        .map(|(crate_name, mut packages)| {
+            packages.sort_by(|a, b| a.version.cmp(&b.version));
+            let versions = packages
+                .into_iter()
+                .map(|package| CrateVersion {
+                    package_name: package.name.clone(),
+                    version: package.version.to_string(),
+                    extern_names: root_deps
+                        .iter()
+                        .filter(|dep| dep.pkg == package.id)
+                        .map(|dep| dep.name.clone())
+                        .collect(),
+                    via: via.remove(&package.id).unwrap_or_default(),
+                })
+                .collect();
+            (crate_name, versions)
+        })
+        .collect())
+}
+
+/// Returns the files that rustdoc's output for `root_package` depends on: the directories
+/// of every local package in its normal dependency graph (including itself), and the
+/// workspace's `Cargo.lock`. Packages from registries and git are identified by their
+/// version in `Cargo.lock`, so their sources don't need to be included.
+pub fn local_sources(metadata: &Metadata, root_package: &Package) -> Result<Vec<PathBuf>> {
+    let resolve = metadata
+        .resolve
+        .as_ref()
+        .ok_or_else(|| anyhow!("Cargo metadata didn't have resolved nodes"))?;
+    let nodes: HashMap<&PackageId, &Node> =
+        resolve.nodes.iter().map(|node| (&node.id, node)).collect();
+
+    let mut sources = BTreeSet::new();
+    let mut visited = HashSet::new();
+    let mut queue = vec![&root_package.id];
+    while let Some(id) = queue.pop() {
+        if !visited.insert(id) {
+            continue;
+        }
+        let node = nodes
+            .get(id)
+            .ok_or_else(|| anyhow!("Failed to find node for package {id}"))?;
+        // Build dependencies are included since build scripts can generate code
+        for dep in &node.deps {
+            if dep
+                .dep_kinds
+                .iter()
+                .any(|info| info.kind != DependencyKind::Development)
+            {
+                queue.push(&dep.pkg);
+            }
+        }
+        let package = &metadata[id];
+        if package.source.is_none() {
+            if let Some(package_path) = package.manifest_path.parent() {
+                sources.insert(PathBuf::from(package_path));
+            }
+        }
+    }
+    sources.insert(metadata.workspace_root.join("Cargo.lock").into());
+    Ok(sources.into_iter().collect())
 }
 
 pub fn handle_failure(operation_name: &str, output: &Output) -> Result<(), anyhow::Error> {
@@ -117,3 +784,73 @@ pub fn capture_error(operation_name: &str, output: &Output) -> anyhow::Error {
     );
     anyhow::Error::msg(message)
 }
+
+/// Returns the paths of the cargo config files that apply to the crate in `crate_path`, whether
+/// or not they exist.
+///
+/// Like cargo, this looks in `crate_path` and every directory above it (which includes the
+/// workspace root), and then in `$CARGO_HOME`.
+fn cargo_config_paths(crate_path: &Path) -> Vec<PathBuf> {
+    let cargo_home = std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".cargo"))
+        });
+    let config_dirs = crate_path
+        .ancestors()
+        .map(|dir| dir.join(".cargo"))
+        .chain(cargo_home);
+    config_dirs
+        .flat_map(|dir| [dir.join("config.toml"), dir.join("config")])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cargo_config_paths, parse_toolchain_list, Toolchain};
+    use std::path::Path;
+
+    #[test]
+    fn toolchain_from_str() {
+        assert_eq!(Toolchain::Auto, "auto".parse().unwrap());
+        assert_eq!(
+            Toolchain::Named("nightly-2023-05-31".into()),
+            "nightly-2023-05-31".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn toolchain_list() {
+        let output = "stable-x86_64-unknown-linux-gnu (default)\n\
+            nightly-x86_64-unknown-linux-gnu\n\
+            nightly-2023-05-31-x86_64-unknown-linux-gnu (active)\n";
+        assert_eq!(
+            vec![
+                "stable-x86_64-unknown-linux-gnu",
+                "nightly-x86_64-unknown-linux-gnu",
+                "nightly-2023-05-31-x86_64-unknown-linux-gnu",
+            ],
+            parse_toolchain_list(output)
+        );
+        assert_eq!(0, parse_toolchain_list("no installed toolchains\n").len());
+    }
+
+    #[test]
+    fn cargo_config_paths_include_ancestors() {
+        let paths = cargo_config_paths(Path::new("/workspace/crate"));
+        for expected in [
+            "/workspace/crate/.cargo/config.toml",
+            "/workspace/crate/.cargo/config",
+            "/workspace/.cargo/config.toml",
+            "/workspace/.cargo/config",
+            "/.cargo/config.toml",
+        ] {
+            assert!(
+                paths.contains(&Path::new(expected).to_path_buf()),
+                "{paths:?}"
+            );
+        }
+    }
+}
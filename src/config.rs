@@ -9,7 +9,7 @@ use std::fmt;
 use wildmatch::WildMatch;
 
 /// Struct reprepsentation of the TOML config files that specify which external types are allowed.
-#[derive(Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct Config {
     /// Whether or not to allow types from `alloc`. Defaults to true.
     #[serde(default = "default_allow_std")]
@@ -57,6 +57,43 @@ impl Config {
                 .any(|glob| glob.matches(type_name)),
         }
     }
+
+    /// Returns the allowed external type glob that most likely was meant to allow the given
+    /// `type_name`, along with a corrected glob that would allow it.
+    ///
+    /// Globs are compared to the type by the path segments before their first wildcard, so
+    /// this finds typos in crate and module names, and modules that were renamed.
+    pub fn similar_allowed_glob(&self, type_name: &str) -> Option<(String, String)> {
+        self.allowed_external_types
+            .iter()
+            .filter_map(|glob| {
+                let glob = glob.to_string();
+                let prefix = match glob.find(['*', '?']) {
+                    // Only whole segments are compared, since a partial segment is expected
+                    // to differ from the type name
+                    Some(index) => &glob[..glob[..index].rfind("::")? + 2],
+                    None => &glob,
+                };
+                let depth = prefix.matches("::").count();
+                let type_prefix = match prefix.ends_with("::") {
+                    true => match type_name.match_indices("::").nth(depth - 1) {
+                        Some((index, _)) => &type_name[..index + 2],
+                        None => type_name,
+                    },
+                    false => type_name,
+                };
+                let distance = strsim::levenshtein(prefix, type_prefix);
+                if distance == 0 || distance > (prefix.chars().count() / 3).max(1) {
+                    return None;
+                }
+                let suggestion = format!("{type_prefix}{}", &glob[prefix.len()..]);
+                WildMatch::new(&suggestion)
+                    .matches(type_name)
+                    .then_some((distance, glob, suggestion))
+            })
+            .min_by_key(|(distance, ..)| *distance)
+            .map(|(_, glob, suggestion)| (glob, suggestion))
+    }
 }
 
 impl Default for Config {
@@ -141,4 +178,38 @@ mod tests {
         assert!(config.allows_type("root", "two::thing"));
         assert!(!config.allows_type("root", "three::thing"));
     }
+
+    #[test]
+    fn test_similar_allowed_glob() {
+        let config = Config {
+            allowed_external_types: vec![
+                WildMatch::new("extrenal_lib::*"),
+                WildMatch::new("other_lib::old_module::*"),
+                WildMatch::new("other_lib::S*"),
+                WildMatch::new("exact_lib::SomeStrcut"),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            Some(("extrenal_lib::*".into(), "external_lib::*".into())),
+            config.similar_allowed_glob("external_lib::SomeStruct")
+        );
+        assert_eq!(
+            Some((
+                "other_lib::old_module::*".into(),
+                "other_lib::new_module::*".into()
+            )),
+            config.similar_allowed_glob("other_lib::new_module::Thing")
+        );
+        assert_eq!(
+            Some((
+                "exact_lib::SomeStrcut".into(),
+                "exact_lib::SomeStruct".into()
+            )),
+            config.similar_allowed_glob("exact_lib::SomeStruct")
+        );
+        // Partial segments before a wildcard aren't compared
+        assert_eq!(None, config.similar_allowed_glob("other_lib::Thing"));
+        assert_eq!(None, config.similar_allowed_glob("unrelated::SomeStruct"));
+    }
 }
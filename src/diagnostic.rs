@@ -0,0 +1,754 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Renders diagnostics with source snippets in the style of rustc's error messages.
+
+use crate::error::ErrorLevel;
+use anyhow::{anyhow, Result};
+use owo_colors::OwoColorize;
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// When to color the output.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ColorChoice {
+    /// Color the output if the stream it's written to supports it, and `NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Returns true if output written to the given stream should be colored.
+    pub fn enabled(self, stream: OutputStream) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                let stream = match stream {
+                    OutputStream::Stdout => supports_color::Stream::Stdout,
+                    OutputStream::Stderr => supports_color::Stream::Stderr,
+                };
+                !no_color() && supports_color::on(stream).is_some()
+            }
+        }
+    }
+
+    /// Applies this choice to the output that's colored with `owo_colors` instead of being
+    /// rendered by a [`Renderer`] (such as the doctor's output).
+    pub fn set_override(self) {
+        match self {
+            Self::Always => owo_colors::set_override(true),
+            Self::Never => owo_colors::set_override(false),
+            Self::Auto if no_color() => owo_colors::set_override(false),
+            Self::Auto => {}
+        }
+    }
+}
+
+/// Returns true if the `NO_COLOR` environment variable asks for output without color.
+fn no_color() -> bool {
+    std::env::var_os("NO_COLOR")
+        .map(|value| !value.is_empty())
+        .unwrap_or(false)
+}
+
+impl fmt::Display for ColorChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Auto => "auto",
+            Self::Always => "always",
+            Self::Never => "never",
+        })
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(anyhow!(
+                "invalid color choice: {s}. Expected `auto`, `always`, or `never`."
+            )),
+        }
+    }
+}
+
+/// Stream that diagnostics are written to.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum OutputStream {
+    #[default]
+    Stdout,
+    Stderr,
+}
+
+impl OutputStream {
+    /// Writes the given text to the stream.
+    pub fn print(self, text: &str) {
+        match self {
+            Self::Stdout => print!("{text}"),
+            Self::Stderr => eprint!("{text}"),
+        }
+    }
+}
+
+impl fmt::Display for OutputStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        })
+    }
+}
+
+impl FromStr for OutputStream {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "stdout" => Ok(Self::Stdout),
+            "stderr" => Ok(Self::Stderr),
+            _ => Err(anyhow!(
+                "invalid output stream: {s}. Expected `stdout` or `stderr`."
+            )),
+        }
+    }
+}
+
+/// Part of a source file to underline, with an optional label.
+///
+/// Positions are `(line, column)` pairs, where lines start at 1 and columns are character
+/// offsets starting at 0. The end is exclusive, like in rustdoc's spans.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Label {
+    begin: (usize, usize),
+    end: (usize, usize),
+    primary: bool,
+    text: Option<String>,
+}
+
+impl Label {
+    /// Creates a label for the main subject of the diagnostic, underlined with `^`.
+    pub fn primary(begin: (usize, usize), end: (usize, usize)) -> Self {
+        Self {
+            begin,
+            end,
+            primary: true,
+            text: None,
+        }
+    }
+
+    /// Creates a label for related code, underlined with `-`.
+    pub fn secondary(begin: (usize, usize), end: (usize, usize)) -> Self {
+        Self {
+            primary: false,
+            ..Self::primary(begin, end)
+        }
+    }
+
+    /// Sets the text shown next to the underline.
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    fn is_multiline(&self) -> bool {
+        self.begin.0 != self.end.0
+    }
+}
+
+/// Labeled lines from a single source file, followed by annotations (the `= ...` lines).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Snippet {
+    file: PathBuf,
+    labels: Vec<Label>,
+    annotations: Vec<String>,
+}
+
+impl Snippet {
+    pub fn new(file: impl Into<PathBuf>) -> Self {
+        Self {
+            file: file.into(),
+            labels: Vec::new(),
+            annotations: Vec::new(),
+        }
+    }
+
+    pub fn with_label(mut self, label: Label) -> Self {
+        self.labels.push(label);
+        self
+    }
+
+    pub fn with_annotation(mut self, annotation: impl Into<String>) -> Self {
+        self.annotations.push(annotation.into());
+        self
+    }
+
+    /// Returns the position of the first primary label (or of the first label, if none are
+    /// primary), formatted with [`fmt_position`].
+    pub fn position(&self) -> Option<String> {
+        self.labels
+            .iter()
+            .find(|label| label.primary)
+            .or_else(|| self.labels.first())
+            .map(|label| fmt_position(&self.file, label.begin))
+    }
+}
+
+/// Formats a position in a source file as `file:line:column`, where both the line and column
+/// start at 1, like in rustc's output. The `position` is a `(line, column)` pair in the form
+/// of rustdoc's spans, where only lines start at 1.
+pub fn fmt_position(file: &Path, position: (usize, usize)) -> String {
+    format!("{}:{}:{}", file.display(), position.0, position.1 + 1)
+}
+
+/// An error or warning with its source snippets, notes, and help.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Diagnostic {
+    level: ErrorLevel,
+    code: Option<String>,
+    message: String,
+    snippets: Vec<Snippet>,
+    notes: Vec<String>,
+    helps: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(level: ErrorLevel, message: impl Into<String>) -> Self {
+        Self {
+            level,
+            code: None,
+            message: message.into(),
+            snippets: Vec::new(),
+            notes: Vec::new(),
+            helps: Vec::new(),
+        }
+    }
+
+    /// Sets the code shown after the level (as in `error[CET0001]`).
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    pub fn with_snippet(mut self, snippet: Snippet) -> Self {
+        self.snippets.push(snippet);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.helps.push(help.into());
+        self
+    }
+
+    /// Returns the position that the diagnostic points to, which is the position of its first
+    /// snippet.
+    pub fn position(&self) -> Option<String> {
+        self.snippets.first().and_then(Snippet::position)
+    }
+}
+
+/// Renders [`Diagnostic`]s as text.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Renderer {
+    color: bool,
+}
+
+impl Renderer {
+    pub fn new(color: bool) -> Self {
+        Self { color }
+    }
+
+    /// Renders a diagnostic, followed by an empty line.
+    ///
+    /// The source file of each snippet is looked up in `sources`, which has either the file's
+    /// contents or the reason it couldn't be loaded.
+    pub fn render(
+        &self,
+        diagnostic: &Diagnostic,
+        sources: &HashMap<PathBuf, Result<String, String>>,
+    ) -> String {
+        let mut out = String::new();
+        let level = match &diagnostic.code {
+            Some(code) => {
+                self.level_style(diagnostic.level, &format!("{}[{code}]", diagnostic.level))
+            }
+            None => self.level_style(diagnostic.level, &diagnostic.level.to_string()),
+        };
+        let message = self.bold(&format!(": {}", diagnostic.message));
+        writeln!(out, "{level}{message}").unwrap();
+
+        // Everything is indented by the width of the widest line number
+        let width = diagnostic
+            .snippets
+            .iter()
+            .flat_map(|snippet| &snippet.labels)
+            .map(|label| label.end.0.max(label.begin.0).to_string().len())
+            .max()
+            .unwrap_or(1);
+        let spacing = " ".repeat(width);
+        for (index, snippet) in diagnostic.snippets.iter().enumerate() {
+            let source = sources
+                .get(&snippet.file)
+                .cloned()
+                .unwrap_or_else(|| Err("not loaded".into()));
+            self.render_snippet(&mut out, diagnostic.level, snippet, source, &spacing, index);
+        }
+        let gutter = self.gutter(&format!("{spacing} ="));
+        for note in &diagnostic.notes {
+            writeln!(out, "{gutter} {}: {note}", self.bold("note")).unwrap();
+        }
+        for help in &diagnostic.helps {
+            writeln!(out, "{gutter} {}: {help}", self.bold("help")).unwrap();
+        }
+        writeln!(out).unwrap();
+        out
+    }
+
+    fn render_snippet(
+        &self,
+        out: &mut String,
+        level: ErrorLevel,
+        snippet: &Snippet,
+        source: Result<String, String>,
+        spacing: &str,
+        index: usize,
+    ) {
+        // Like rustc, the first snippet's location is marked with an arrow
+        let arrow = if index == 0 { "-->" } else { ":::" };
+        let position = snippet
+            .position()
+            .unwrap_or_else(|| snippet.file.display().to_string());
+        writeln!(out, "{spacing}{} {position}", self.gutter(arrow)).unwrap();
+        let bar = self.gutter(&format!("{spacing} |"));
+        writeln!(out, "{bar}").unwrap();
+        match source {
+            Ok(source) if !snippet.labels.is_empty() => {
+                self.render_lines(out, level, snippet, &source, spacing);
+                writeln!(out, "{bar}").unwrap();
+            }
+            Ok(_) => {}
+            Err(err) => {
+                writeln!(out, "{bar} failed to load the source for context: {err}").unwrap();
+                writeln!(out, "{bar}").unwrap();
+            }
+        }
+        let gutter = self.gutter(&format!("{spacing} ="));
+        for annotation in &snippet.annotations {
+            writeln!(out, "{gutter} {annotation}").unwrap();
+        }
+    }
+
+    fn render_lines(
+        &self,
+        out: &mut String,
+        level: ErrorLevel,
+        snippet: &Snippet,
+        source: &str,
+        spacing: &str,
+    ) {
+        let lines: Vec<&str> = source.lines().collect();
+        let has_multiline = snippet.labels.iter().any(Label::is_multiline);
+
+        // Long multi-line labels only show their first and last lines
+        let mut shown = BTreeSet::new();
+        for label in &snippet.labels {
+            if label.end.0 - label.begin.0 > 3 {
+                shown.extend([
+                    label.begin.0,
+                    label.begin.0 + 1,
+                    label.end.0 - 1,
+                    label.end.0,
+                ]);
+            } else {
+                shown.extend(label.begin.0..=label.end.0);
+            }
+        }
+
+        let bar = self.gutter(&format!("{spacing} |"));
+        let mut previous: Option<usize> = None;
+        for &number in &shown {
+            let Some(line) = lines.get(number.wrapping_sub(1)) else {
+                continue;
+            };
+            // Multi-line labels that the line is inside of draw a line down the gutter
+            let inside = |number: usize| {
+                snippet
+                    .labels
+                    .iter()
+                    .filter(|label| label.is_multiline())
+                    .find(|label| label.begin.0 <= number && number <= label.end.0)
+            };
+            if previous
+                .map(|previous| previous + 1 < number)
+                .unwrap_or(false)
+            {
+                let marker = match inside(number) {
+                    Some(label) if label.begin.0 < number => self.underline(level, label, "|"),
+                    _ => " ".into(),
+                };
+                let marker = if has_multiline {
+                    format!(" {marker}")
+                } else {
+                    "".into()
+                };
+                writeln!(out, "{}{marker}", self.gutter("...")).unwrap();
+            }
+            let marker = match inside(number) {
+                Some(label) if label.begin.0 == number => {
+                    format!("{} ", self.underline(level, label, "/"))
+                }
+                Some(label) => format!("{} ", self.underline(level, label, "|")),
+                None if has_multiline => "  ".into(),
+                None => "".into(),
+            };
+            let number_text = self.gutter(&format!("{number:>width$} |", width = spacing.len()));
+            writeln!(out, "{number_text} {marker}{}", expand_tabs(line)).unwrap();
+
+            let gutter_marker = match inside(number) {
+                Some(label) if label.end.0 != number => {
+                    format!("{} ", self.underline(level, label, "|"))
+                }
+                _ if has_multiline => "  ".into(),
+                _ => "".into(),
+            };
+            self.render_underlines(out, level, snippet, line, number, &bar, &gutter_marker);
+
+            // Multi-line labels end with an underline up to their last character
+            for label in snippet
+                .labels
+                .iter()
+                .filter(|label| label.is_multiline() && label.end.0 == number)
+            {
+                let end = display_column(line, label.end.1.max(1) - 1);
+                let underline = format!("|{}^", "_".repeat(end + 1));
+                let text = label
+                    .text
+                    .as_ref()
+                    .map(|text| format!(" {text}"))
+                    .unwrap_or_default();
+                writeln!(
+                    out,
+                    "{bar} {}{}",
+                    self.underline(level, label, &underline),
+                    self.underline(level, label, &text)
+                )
+                .unwrap();
+            }
+            previous = Some(number);
+        }
+    }
+
+    /// Renders the underlines of the single-line labels on a line, along with their text. When
+    /// more than one label has text, the text of the rightmost one follows its underline, and
+    /// the others are put on their own lines below.
+    ///
+    /// Labels that overlap one before them (including ones that start at the same column) aren't
+    /// drawn, and neither is their text.
+    #[allow(clippy::too_many_arguments)]
+    fn render_underlines(
+        &self,
+        out: &mut String,
+        level: ErrorLevel,
+        snippet: &Snippet,
+        line: &str,
+        number: usize,
+        bar: &str,
+        gutter_marker: &str,
+    ) {
+        let mut labels: Vec<&Label> = snippet
+            .labels
+            .iter()
+            .filter(|label| !label.is_multiline() && label.begin.0 == number)
+            .collect();
+        if labels.is_empty() {
+            return;
+        }
+        // The longest of the labels that start at the same column is the one that's drawn
+        labels.sort_by_key(|label| (label.begin.1, Reverse(label.end.1)));
+
+        let mut underline = String::new();
+        let mut column = 0;
+        // Labels with text that were drawn, with their starting columns, which always increase
+        let mut texts: Vec<(usize, &Label)> = Vec::new();
+        for label in &labels {
+            let start = display_column(line, label.begin.1);
+            let end = display_column(line, label.end.1).max(start + 1);
+            if start < column {
+                // Overlapping labels are drawn over by the ones before them
+                continue;
+            }
+            underline.push_str(&" ".repeat(start - column));
+            let mark = if label.primary { "^" } else { "-" };
+            underline.push_str(&self.underline(level, label, &mark.repeat(end - start)));
+            column = end;
+            if label.text.is_some() {
+                texts.push((start, label));
+            }
+        }
+        if let Some((_, last)) = texts.pop() {
+            let text = last.text.as_deref().unwrap_or_default();
+            underline.push_str(&format!(" {}", self.underline(level, last, text)));
+        }
+        writeln!(out, "{bar} {gutter_marker}{underline}").unwrap();
+        if texts.is_empty() {
+            return;
+        }
+
+        let connectors = |count: usize| {
+            let mut text = String::new();
+            let mut column = 0;
+            for (start, label) in &texts[..count] {
+                text.push_str(&" ".repeat(start - column));
+                text.push_str(&self.underline(level, label, "|"));
+                column = start + 1;
+            }
+            (text, column)
+        };
+        writeln!(out, "{bar} {gutter_marker}{}", connectors(texts.len()).0).unwrap();
+        for index in (0..texts.len()).rev() {
+            let (mut text, column) = connectors(index);
+            let (start, label) = texts[index];
+            text.push_str(&" ".repeat(start - column));
+            let label_text = label.text.as_deref().unwrap_or_default();
+            text.push_str(&self.underline(level, label, label_text));
+            writeln!(out, "{bar} {gutter_marker}{text}").unwrap();
+        }
+    }
+
+    fn level_style(&self, level: ErrorLevel, text: &str) -> String {
+        match (self.color, level) {
+            (false, _) => text.into(),
+            (true, ErrorLevel::Error) => text.red().bold().to_string(),
+            (true, ErrorLevel::Warning) => text.yellow().bold().to_string(),
+        }
+    }
+
+    fn underline(&self, level: ErrorLevel, label: &Label, text: &str) -> String {
+        match (self.color, label.primary) {
+            (false, _) => text.into(),
+            (true, true) => self.level_style(level, text),
+            (true, false) => text.blue().bold().to_string(),
+        }
+    }
+
+    fn gutter(&self, text: &str) -> String {
+        if self.color {
+            text.blue().bold().to_string()
+        } else {
+            text.into()
+        }
+    }
+
+    fn bold(&self, text: &str) -> String {
+        if self.color {
+            text.bold().to_string()
+        } else {
+            text.into()
+        }
+    }
+}
+
+/// Tabs are shown as four spaces so that underlines line up regardless of the terminal.
+fn expand_tabs(line: &str) -> String {
+    line.replace('\t', "    ")
+}
+
+/// Returns the column that the character at the given offset is shown at after expanding tabs.
+fn display_column(line: &str, offset: usize) -> usize {
+    let mut chars = line.chars();
+    let mut column = 0;
+    for _ in 0..offset {
+        column += match chars.next() {
+            Some('\t') => 4,
+            _ => 1,
+        };
+    }
+    column
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Label, Renderer, Snippet};
+    use crate::error::ErrorLevel;
+    use pretty_assertions::assert_str_eq;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    const SOURCE: &str = "\
+pub struct Foo {
+    pub a: Option<ext::A>,
+\tpub b: (ext::A, ext::B),
+}
+
+pub fn f() {}
+";
+
+    fn render(diagnostic: &Diagnostic) -> String {
+        let mut sources = HashMap::new();
+        sources.insert(PathBuf::from("src/lib.rs"), Ok(SOURCE.to_string()));
+        Renderer::new(false).render(diagnostic, &sources)
+    }
+
+    #[test]
+    fn single_label() {
+        let diagnostic = Diagnostic::new(ErrorLevel::Error, "Unapproved external type")
+            .with_code("CET0001")
+            .with_snippet(
+                Snippet::new("src/lib.rs")
+                    .with_label(Label::primary((2, 18), (2, 24)))
+                    .with_annotation("in struct field of `Foo::a`"),
+            )
+            .with_note("only found with feature `a`")
+            .with_help("allow it in the config");
+        assert_str_eq!(
+            "\
+error[CET0001]: Unapproved external type
+ --> src/lib.rs:2:19
+  |
+2 |     pub a: Option<ext::A>,
+  |                   ^^^^^^
+  |
+  = in struct field of `Foo::a`
+  = note: only found with feature `a`
+  = help: allow it in the config
+
+",
+            render(&diagnostic)
+        );
+    }
+
+    #[test]
+    fn several_labels() {
+        let diagnostic = Diagnostic::new(ErrorLevel::Warning, "Types").with_snippet(
+            Snippet::new("src/lib.rs")
+                .with_label(Label::primary((2, 18), (2, 24)).with_text("first"))
+                .with_label(Label::primary((3, 9), (3, 15)).with_text("second"))
+                .with_label(Label::secondary((3, 17), (3, 23)).with_text("third")),
+        );
+        assert_str_eq!(
+            "\
+warning: Types
+ --> src/lib.rs:2:19
+  |
+2 |     pub a: Option<ext::A>,
+  |                   ^^^^^^ first
+3 |     pub b: (ext::A, ext::B),
+  |             ^^^^^^  ------ third
+  |             |
+  |             second
+  |
+
+",
+            render(&diagnostic)
+        );
+    }
+
+    #[test]
+    fn labels_at_same_column() {
+        let diagnostic = Diagnostic::new(ErrorLevel::Warning, "Types").with_snippet(
+            Snippet::new("src/lib.rs")
+                .with_label(Label::primary((3, 9), (3, 15)).with_text("first"))
+                .with_label(Label::primary((3, 9), (3, 15)).with_text("second"))
+                .with_label(Label::secondary((3, 9), (3, 12)).with_text("third"))
+                .with_label(Label::secondary((3, 17), (3, 23)).with_text("fourth")),
+        );
+        assert_str_eq!(
+            "\
+warning: Types
+ --> src/lib.rs:3:10
+  |
+3 |     pub b: (ext::A, ext::B),
+  |             ^^^^^^  ------ fourth
+  |             |
+  |             first
+  |
+
+",
+            render(&diagnostic)
+        );
+    }
+
+    #[test]
+    fn multiline_label() {
+        let diagnostic = Diagnostic::new(ErrorLevel::Error, "Item")
+            .with_snippet(
+                Snippet::new("src/lib.rs")
+                    .with_label(Label::primary((1, 0), (4, 1)).with_text("in here")),
+            )
+            .with_snippet(Snippet::new("src/lib.rs").with_label(Label::secondary((6, 7), (6, 8))));
+        assert_str_eq!(
+            "\
+error: Item
+ --> src/lib.rs:1:1
+  |
+1 | / pub struct Foo {
+2 | |     pub a: Option<ext::A>,
+3 | |     pub b: (ext::A, ext::B),
+4 | | }
+  | |_^ in here
+  |
+ ::: src/lib.rs:6:8
+  |
+6 | pub fn f() {}
+  |        -
+  |
+
+",
+            render(&diagnostic)
+        );
+    }
+
+    #[test]
+    fn position_is_one_based() {
+        let diagnostic = Diagnostic::new(ErrorLevel::Error, "Types").with_snippet(
+            Snippet::new("src/lib.rs")
+                .with_label(Label::secondary((2, 0), (2, 3)))
+                .with_label(Label::primary((3, 9), (3, 15))),
+        );
+        assert_eq!(Some("src/lib.rs:3:10".into()), diagnostic.position());
+        assert_eq!(
+            None,
+            Diagnostic::new(ErrorLevel::Error, "Types")
+                .with_snippet(Snippet::new("src/lib.rs"))
+                .position()
+        );
+    }
+
+    #[test]
+    fn missing_source() {
+        let diagnostic = Diagnostic::new(ErrorLevel::Error, "Missing").with_snippet(
+            Snippet::new("src/other.rs")
+                .with_label(Label::primary((2, 0), (2, 1)))
+                .with_annotation("in `f`"),
+        );
+        assert_str_eq!(
+            "\
+error: Missing
+ --> src/other.rs:2:1
+  |
+  | failed to load the source for context: not loaded
+  |
+  = in `f`
+
+",
+            render(&diagnostic)
+        );
+    }
+}
@@ -0,0 +1,335 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::cargo::{probe_format_version, rustdoc_version, Toolchain};
+use crate::config::Config;
+use crate::rustdoc_json::{self, SUPPORTED_FORMAT_VERSIONS};
+use owo_colors::{OwoColorize, Stream};
+use std::fmt;
+use std::fs;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Outcome of a single diagnostic check.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+/// Result of a single diagnostic check.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Check {
+    pub status: Status,
+    pub name: &'static str,
+    pub detail: String,
+}
+
+impl Check {
+    fn new(status: Status, name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            status,
+            name,
+            detail: detail.into(),
+        }
+    }
+}
+
+/// Diagnoses the environment that the tool runs in, to explain the most common reasons
+/// that it fails before any types are checked.
+#[derive(Debug)]
+pub struct Doctor {
+    /// Toolchain that rustdoc would be run with
+    pub toolchain: Toolchain,
+    /// Whether `RUSTC_BOOTSTRAP=1` would be set for rustdoc
+    pub allow_bootstrap: bool,
+    /// Path of the crate to examine
+    pub crate_path: PathBuf,
+    /// Directory to document probe crates in
+    pub probe_path: PathBuf,
+    /// Paths that rustdoc's JSON output would be read from (one per target)
+    pub json_paths: Vec<PathBuf>,
+    /// Features that would be enabled
+    pub features: Vec<String>,
+    /// Path of the config file, if one was given
+    pub config_path: Option<PathBuf>,
+}
+
+impl Doctor {
+    /// Runs every check.
+    pub fn diagnose(&self) -> Diagnosis {
+        let mut checks = Vec::new();
+        self.check_toolchain(&mut checks);
+        for json_path in &self.json_paths {
+            checks.push(check_json_path(json_path));
+        }
+        checks.push(Check::new(
+            Status::Ok,
+            "features",
+            if self.features.is_empty() {
+                "none".to_string()
+            } else {
+                self.features.join(", ")
+            },
+        ));
+        checks.push(self.check_config());
+        Diagnosis { checks }
+    }
+
+    fn check_toolchain(&self, checks: &mut Vec<Check>) {
+        let toolchain = match self.toolchain.clone().resolve(
+            &self.crate_path,
+            &self.probe_path,
+            self.allow_bootstrap,
+        ) {
+            Ok(toolchain) => toolchain,
+            Err(err) => {
+                checks.push(Check::new(Status::Error, "toolchain", format!("{err:#}")));
+                return;
+            }
+        };
+        let name = match &toolchain {
+            Toolchain::Named(name) => Some(name.clone()),
+            Toolchain::Current | Toolchain::Auto => self.active_toolchain(),
+        };
+        match rustdoc_version(&toolchain, &self.crate_path) {
+            Ok(version) => checks.push(Check::new(
+                Status::Ok,
+                "toolchain",
+                match name {
+                    Some(name) => format!("{name} ({version})"),
+                    None => version,
+                },
+            )),
+            Err(err) => {
+                checks.push(Check::new(Status::Error, "toolchain", format!("{err:#}")));
+                return;
+            }
+        }
+
+        match probe_format_version(
+            &toolchain,
+            &self.crate_path,
+            &self.probe_path,
+            self.allow_bootstrap,
+        ) {
+            Ok(format_version) => {
+                checks.push(Check::new(Status::Ok, "rustdoc JSON output", "supported"));
+                checks.push(if SUPPORTED_FORMAT_VERSIONS.contains(&format_version) {
+                    Check::new(
+                        Status::Ok,
+                        "format version",
+                        format!(
+                            "{format_version} (supported: {})",
+                            rustdoc_json::supported_format_versions()
+                        ),
+                    )
+                } else {
+                    Check::new(
+                        Status::Error,
+                        "format version",
+                        format!(
+                            "{format_version} isn't supported (supported: {}); run with \
+                            `--toolchain auto` to search the installed toolchains for one \
+                            that matches",
+                            rustdoc_json::supported_format_versions()
+                        ),
+                    )
+                });
+            }
+            Err(err) => checks.push(Check::new(
+                Status::Error,
+                "rustdoc JSON output",
+                format!(
+                    "not supported by this toolchain ({}); use a nightly toolchain, or opt \
+                    into unstable features with `--allow-bootstrap`",
+                    rustdoc_error_summary(&err)
+                ),
+            )),
+        }
+    }
+
+    /// Returns the name of the active rustup toolchain in the crate's directory.
+    fn active_toolchain(&self) -> Option<String> {
+        let output = Command::new("rustup")
+            .current_dir(&self.crate_path)
+            .arg("show")
+            .arg("active-toolchain")
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8_lossy(&output.stdout)
+            .split_whitespace()
+            .next()
+            .map(String::from)
+    }
+
+    fn check_config(&self) -> Check {
+        let Some(config_path) = &self.config_path else {
+            return Check::new(
+                Status::Ok,
+                "config file",
+                "none (only types from `std`, `alloc`, and `core` are allowed)",
+            );
+        };
+        let config = fs::read_to_string(config_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(toml::from_str::<Config>(&contents)?));
+        match config {
+            Ok(config) => Check::new(
+                Status::Ok,
+                "config file",
+                format!(
+                    "{} ({} allowed external type patterns)",
+                    config_path.display(),
+                    config.allowed_external_types.len()
+                ),
+            ),
+            Err(err) => Check::new(
+                Status::Error,
+                "config file",
+                format!("{}: {err}", config_path.display()),
+            ),
+        }
+    }
+}
+
+fn check_json_path(json_path: &std::path::Path) -> Check {
+    if json_path.exists() {
+        let format_version = fs::File::open(json_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| rustdoc_json::format_version(BufReader::new(json)));
+        return match format_version {
+            Ok(format_version) => Check::new(
+                Status::Ok,
+                "JSON output path",
+                format!(
+                    "{} (last written with format version {format_version})",
+                    json_path.display()
+                ),
+            ),
+            Err(err) => Check::new(
+                Status::Warning,
+                "JSON output path",
+                format!("{} (unreadable: {err})", json_path.display()),
+            ),
+        };
+    }
+    // If rustdoc wrote other JSON files, then the crate name is probably not what's expected
+    let mut others: Vec<String> = json_path
+        .parent()
+        .and_then(|doc_path| fs::read_dir(doc_path).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| name.ends_with(".json"))
+        .collect();
+    others.sort();
+    if others.is_empty() {
+        Check::new(
+            Status::Ok,
+            "JSON output path",
+            format!("{} (not generated yet)", json_path.display()),
+        )
+    } else {
+        Check::new(
+            Status::Warning,
+            "JSON output path",
+            format!(
+                "{} doesn't exist, but the same directory has {}",
+                json_path.display(),
+                others.join(", ")
+            ),
+        )
+    }
+}
+
+/// Returns the first error line from rustdoc's output in a failure captured by
+/// [`capture_error`](crate::cargo::capture_error), or the whole error otherwise.
+fn rustdoc_error_summary(err: &anyhow::Error) -> String {
+    let message = format!("{err:#}");
+    message
+        .lines()
+        .map(|line| line.trim_start_matches("Stderr: "))
+        .find(|line| line.starts_with("error"))
+        .unwrap_or_else(|| message.lines().next().unwrap_or_default())
+        .to_string()
+}
+
+/// Results of all of the checks run by a [`Doctor`].
+#[derive(Debug)]
+pub struct Diagnosis {
+    checks: Vec<Check>,
+}
+
+impl Diagnosis {
+    pub fn checks(&self) -> &[Check] {
+        &self.checks
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.checks
+            .iter()
+            .any(|check| check.status == Status::Error)
+    }
+}
+
+impl fmt::Display for Diagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let status = match check.status {
+                Status::Ok => format!(
+                    "{}",
+                    "ok:     ".if_supports_color(Stream::Stdout, |text| text.green())
+                ),
+                Status::Warning => format!(
+                    "{}",
+                    "warning:".if_supports_color(Stream::Stdout, |text| text.yellow())
+                ),
+                Status::Error => format!(
+                    "{}",
+                    "error:  ".if_supports_color(Stream::Stdout, |text| text.red())
+                ),
+            };
+            writeln!(f, "{status} {}: {}", check.name, check.detail)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_json_path, rustdoc_error_summary, Status};
+    use anyhow::anyhow;
+    use std::path::Path;
+
+    #[test]
+    fn error_summary() {
+        let err = anyhow!(
+            "Failed to rustdoc:\nStatus: 1\nStdout: \nStderr: error: the option `Z` is only \
+            accepted on the nightly compiler\n\nhelp: consider switching to a nightly toolchain"
+        );
+        assert_eq!(
+            "error: the option `Z` is only accepted on the nightly compiler",
+            rustdoc_error_summary(&err)
+        );
+        assert_eq!(
+            "failed to run rustdoc",
+            rustdoc_error_summary(&anyhow!("failed to run rustdoc"))
+        );
+    }
+
+    #[test]
+    fn missing_json_path() {
+        let check = check_json_path(Path::new("does/not/exist.json"));
+        assert_eq!(Status::Ok, check.status);
+        assert_eq!("does/not/exist.json (not generated yet)", check.detail);
+    }
+}
@@ -4,16 +4,18 @@
  */
 
 use crate::bug;
-use anyhow::{Context, Result};
-use pest::Position;
+use crate::config::Config;
+use crate::diagnostic::{ColorChoice, Diagnostic, Label, OutputStream, Renderer, Snippet};
+use anyhow::anyhow;
 use rustdoc_types::Span;
 use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::BTreeSet;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::iter::Iterator;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 /// Where the error occurred relative to the [`Path`](crate::path::Path).
 ///
@@ -21,22 +23,16 @@ use std::path::{Path, PathBuf};
 /// specific about that function, such as a specific function argument that is in error.
 ///
 /// There is overlap in this enum with [`ComponentType`](crate::path::ComponentType) since
-/// some paths are specific enough to locate the external type.
+/// some paths are specific enough to locate the external type. Where the type is nested
+/// inside of another type at that location is given by a chain of [`TypePosition`]s.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum ErrorLocation {
     AssocType,
     ArgumentNamed(String),
-    ClosureInput,
-    ClosureOutput,
     ConstGeneric,
     Constant,
-    DynTrait,
-    EnumTupleEntry,
-    GenericArg,
     GenericDefaultBinding,
     ImplementedTrait,
-    QualifiedSelfType,
-    QualifiedSelfTypeAsTrait,
     ReExport,
     ReturnValue,
     Static,
@@ -51,17 +47,10 @@ impl fmt::Display for ErrorLocation {
         let s = match self {
             Self::AssocType => "associated type",
             Self::ArgumentNamed(name) => return write!(f, "argument named `{}` of", name),
-            Self::ClosureInput => "closure input of",
-            Self::ClosureOutput => "closure output of",
             Self::ConstGeneric => "const generic of",
             Self::Constant => "constant",
-            Self::DynTrait => "dyn trait of",
-            Self::EnumTupleEntry => "enum tuple entry of",
-            Self::GenericArg => "generic arg of",
             Self::GenericDefaultBinding => "generic default binding of",
             Self::ImplementedTrait => "implemented trait of",
-            Self::QualifiedSelfType => "qualified self type",
-            Self::QualifiedSelfTypeAsTrait => "qualified type `as` trait",
             Self::ReExport => "re-export named",
             Self::ReturnValue => "return value of",
             Self::Static => "static value",
@@ -74,9 +63,76 @@ impl fmt::Display for ErrorLocation {
     }
 }
 
+impl ErrorLocation {
+    /// Returns the name of this kind of location, without any details specific to the item
+    /// (such as argument names).
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::AssocType => "associated type",
+            Self::ArgumentNamed(_) => "argument",
+            Self::ConstGeneric => "const generic",
+            Self::Constant => "constant",
+            Self::GenericDefaultBinding => "generic default binding",
+            Self::ImplementedTrait => "implemented trait",
+            Self::ReExport => "re-export",
+            Self::ReturnValue => "return value",
+            Self::Static => "static value",
+            Self::StructField => "struct field",
+            Self::TraitBound => "trait bound",
+            Self::TypeDef => "typedef type",
+            Self::WhereBound => "where bound",
+        }
+    }
+}
+
+/// Position of a type nested inside of another type.
+///
+/// For example, `ext::Error` in a field of type `Option<Result<(), ext::Error>>` is at
+/// generic arg 0 of `Option`, and then at generic arg 1 of `Result`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum TypePosition {
+    /// Associated type binding (such as `Item = T` or `Item: Trait`) of the named trait
+    AssocBinding { name: String, of: String },
+    /// Input of a function pointer or `Fn` trait
+    FnInput { index: usize, of: String },
+    /// Output of a function pointer or `Fn` trait
+    FnOutput { of: String },
+    /// Generic argument (counting lifetimes) of the named type or trait
+    GenericArg { index: usize, of: String },
+    /// Self type of a qualified path (such as `<T as Trait>::Assoc`)
+    QualifiedSelfType,
+    /// Trait of a qualified path
+    QualifiedTrait,
+    /// Trait of a trait object (such as `dyn Trait`)
+    TraitObject,
+    /// Entry of a tuple
+    TupleEntry(usize),
+}
+
+impl fmt::Display for TypePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AssocBinding { name, of } => write!(f, "binding `{name}` of `{of}`"),
+            Self::FnInput { index, of } => write!(f, "input {index} of `{of}`"),
+            Self::FnOutput { of } => write!(f, "output of `{of}`"),
+            Self::GenericArg { index, of } => write!(f, "generic arg {index} of `{of}`"),
+            Self::QualifiedSelfType => write!(f, "self type of qualified path"),
+            Self::QualifiedTrait => write!(f, "trait of qualified path"),
+            Self::TraitObject => write!(f, "trait object"),
+            Self::TupleEntry(index) => write!(f, "tuple entry {index}"),
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct ValidationErrors {
     errors: BTreeSet<ValidationError>,
+    /// Names of the configurations (such as feature sets) that each error was found in,
+    /// keyed by the error's sort key.
+    ///
+    /// This is only populated by [`ValidationErrors::merge`], and only for errors that
+    /// weren't found in every configuration.
+    found_only_in: HashMap<String, Vec<String>>,
 }
 
 impl ValidationErrors {
@@ -84,6 +140,39 @@ impl ValidationErrors {
         Default::default()
     }
 
+    /// Merges the errors found by checking a crate in several configurations (such as
+    /// different feature sets) into a single set of errors.
+    ///
+    /// Each configuration is given as a name and the errors found in it. Errors that were
+    /// only found in some of the configurations are tagged with the names of those
+    /// configurations, which can be retrieved with [`ValidationErrors::found_only_in`].
+    pub fn merge(runs: impl IntoIterator<Item = (String, ValidationErrors)>) -> Self {
+        let mut merged = ValidationErrors::new();
+        let mut found_in: HashMap<String, Vec<String>> = HashMap::new();
+        let mut run_count = 0;
+        for (name, errors) in runs {
+            run_count += 1;
+            for error in errors.errors {
+                found_in
+                    .entry(error.sort_key().to_string())
+                    .or_default()
+                    .push(name.clone());
+                merged.errors.insert(error);
+            }
+        }
+        found_in.retain(|_, names| names.len() < run_count);
+        merged.found_only_in = found_in;
+        merged
+    }
+
+    /// Returns the names of the configurations the given error was found in, or `None`
+    /// if it was found in all of them.
+    pub fn found_only_in(&self, error: &ValidationError) -> Option<&[String]> {
+        self.found_only_in
+            .get(error.sort_key())
+            .map(|names| names.as_slice())
+    }
+
     pub fn error_count(&self) -> usize {
         self.errors
             .iter()
@@ -113,19 +202,125 @@ impl ValidationErrors {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ErrorLevel {
     Error,
     Warning,
 }
 
+impl fmt::Display for ErrorLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Error => "error",
+            Self::Warning => "warning",
+        })
+    }
+}
+
+/// Stable code for each kind of [`ValidationError`], which is shown in its headline and can be
+/// explained with `--explain`.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum ErrorCode {
+    /// `CET0001`: an unapproved external type is referenced in the public API
+    UnapprovedExternalType,
+    /// `CET0002`: a struct has fields that are hidden from the docs
+    FieldsStripped,
+    /// `CET0003`: an item is re-exported from a hidden module
+    HiddenModule,
+    /// `CET0004`: types from more than one version of a crate are in the public API
+    DuplicateCrateVersions,
+}
+
+impl ErrorCode {
+    pub const ALL: [ErrorCode; 4] = [
+        Self::UnapprovedExternalType,
+        Self::FieldsStripped,
+        Self::HiddenModule,
+        Self::DuplicateCrateVersions,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::UnapprovedExternalType => "CET0001",
+            Self::FieldsStripped => "CET0002",
+            Self::HiddenModule => "CET0003",
+            Self::DuplicateCrateVersions => "CET0004",
+        }
+    }
+
+    /// Returns the long-form description of the error, explaining why it matters and how to
+    /// fix it.
+    pub fn explanation(self) -> &'static str {
+        match self {
+            Self::UnapprovedExternalType => include_str!("explanations/CET0001.md"),
+            Self::FieldsStripped => include_str!("explanations/CET0002.md"),
+            Self::HiddenModule => include_str!("explanations/CET0003.md"),
+            Self::DuplicateCrateVersions => include_str!("explanations/CET0004.md"),
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|code| code.as_str().eq_ignore_ascii_case(s))
+            .ok_or_else(|| {
+                let codes: Vec<&str> = Self::ALL.iter().map(|code| code.as_str()).collect();
+                anyhow!(
+                    "unknown error code: {s}. Expected one of {}.",
+                    codes.join(", ")
+                )
+            })
+    }
+}
+
+/// A reference to a type from one version of a crate, for
+/// [`ValidationError::DuplicateCrateVersions`].
+#[derive(Clone, Debug)]
+pub struct CrateUsage {
+    pub type_name: String,
+    /// Which version of the crate the type is from (such as `http 0.2.9, via hyper`), if it's
+    /// known
+    pub version: Option<String>,
+    pub what: ErrorLocation,
+    pub in_what_type: String,
+    pub location: Option<Span>,
+}
+
+impl fmt::Display for CrateUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`", self.type_name)?;
+        if let Some(version) = &self.version {
+            write!(f, " ({version})")?;
+        }
+        write!(f, " in {} `{}`", self.what, self.in_what_type)
+    }
+}
+
 /// Error type for validation errors that get displayed to the user on the CLI.
 #[derive(Debug)]
 pub enum ValidationError {
     UnapprovedExternalTypeRef {
         type_name: String,
         what: ErrorLocation,
+        /// Positions of the type within the type at `what`, from outermost to innermost
+        chain: Vec<TypePosition>,
         in_what_type: String,
+        /// Other public paths of `in_what_type`, since items can be re-exported under more than
+        /// one name
+        other_paths: Vec<String>,
+        /// Top-level module of the root crate that `in_what_type` is in, or `None` if it's at
+        /// the crate root
+        top_level_module: Option<String>,
         location: Option<Span>,
         sort_key: String,
     },
@@ -139,20 +334,29 @@ pub enum ValidationError {
         location: Option<Span>,
         hidden_module: Option<String>,
     },
+    DuplicateCrateVersions {
+        crate_name: String,
+        versions: Vec<String>,
+        usages: Vec<CrateUsage>,
+        location: Option<Span>,
+        sort_key: String,
+    },
 }
 
 impl ValidationError {
     pub fn unapproved_external_type_ref(
         type_name: impl Into<String>,
         what: &ErrorLocation,
+        chain: &[TypePosition],
         in_what_type: impl Into<String>,
         location: Option<&Span>,
     ) -> Self {
         let type_name = type_name.into();
         let in_what_type = in_what_type.into();
         let sort_key = format!(
-            "{}:{type_name}:{what}:{in_what_type}",
-            location_sort_key(location)
+            "{}:{type_name}:{what}:{in_what_type}:{}",
+            location_sort_key(location),
+            fmt_chain(chain)
         );
         if location.is_none() {
             bug!("An error is missing a span and will be printed without context, file name, and line number.");
@@ -160,15 +364,111 @@ impl ValidationError {
         Self::UnapprovedExternalTypeRef {
             type_name,
             what: what.clone(),
+            chain: chain.to_vec(),
             in_what_type,
+            other_paths: Vec::new(),
+            top_level_module: None,
             location: location.cloned(),
             sort_key,
         }
     }
 
+    /// Sets the other public paths that the item with the unapproved reference is reachable
+    /// through, in addition to the one it's named by.
+    pub fn with_other_paths(mut self, paths: Vec<String>) -> Self {
+        if let Self::UnapprovedExternalTypeRef { other_paths, .. } = &mut self {
+            *other_paths = paths;
+        }
+        self
+    }
+
+    /// Returns the other public paths that the item in error is reachable through.
+    pub fn other_paths(&self) -> &[String] {
+        match self {
+            Self::UnapprovedExternalTypeRef { other_paths, .. } => other_paths,
+            _ => &[],
+        }
+    }
+
+    /// Sets the top-level module of the root crate that the item with the unapproved reference
+    /// is in.
+    pub fn with_top_level_module(mut self, module: Option<String>) -> Self {
+        if let Self::UnapprovedExternalTypeRef {
+            top_level_module, ..
+        } = &mut self
+        {
+            *top_level_module = module;
+        }
+        self
+    }
+
+    /// Returns the top-level module of the root crate that the item in error is in, or `None`
+    /// if it's at the crate root or isn't an unapproved reference.
+    pub fn top_level_module(&self) -> Option<&str> {
+        match self {
+            Self::UnapprovedExternalTypeRef {
+                top_level_module, ..
+            } => top_level_module.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Returns the path of the item the error is in, if it's in an item.
+    pub fn in_what_type(&self) -> Option<&str> {
+        match self {
+            Self::UnapprovedExternalTypeRef { in_what_type, .. }
+            | Self::HiddenModule { in_what_type, .. } => Some(in_what_type),
+            Self::FieldsStripped { .. } | Self::DuplicateCrateVersions { .. } => None,
+        }
+    }
+
+    /// Returns where in the item the error is, if it's in an item.
+    pub fn what(&self) -> Option<&ErrorLocation> {
+        match self {
+            Self::UnapprovedExternalTypeRef { what, .. } | Self::HiddenModule { what, .. } => {
+                Some(what)
+            }
+            Self::FieldsStripped { .. } | Self::DuplicateCrateVersions { .. } => None,
+        }
+    }
+
+    /// Creates an error for when types from more than one version of the same crate
+    /// are referenced in the public API.
+    ///
+    /// The `usages` are one reference to each of the versions, and `versions` are the
+    /// versions of that crate found in the dependency graph. The error is located at the
+    /// first usage.
+    pub fn duplicate_crate_versions(
+        crate_name: impl Into<String>,
+        versions: Vec<String>,
+        usages: Vec<CrateUsage>,
+    ) -> Self {
+        let crate_name = crate_name.into();
+        let location = usages.first().and_then(|usage| usage.location.clone());
+        let sort_key = format!("{}:{crate_name}", location_sort_key(location.as_ref()));
+        Self::DuplicateCrateVersions {
+            crate_name,
+            versions,
+            usages,
+            location,
+            sort_key,
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnapprovedExternalTypeRef { .. } => ErrorCode::UnapprovedExternalType,
+            Self::FieldsStripped { .. } => ErrorCode::FieldsStripped,
+            Self::HiddenModule { .. } => ErrorCode::HiddenModule,
+            Self::DuplicateCrateVersions { .. } => ErrorCode::DuplicateCrateVersions,
+        }
+    }
+
     pub fn level(&self) -> ErrorLevel {
         match self {
-            Self::UnapprovedExternalTypeRef { .. } => ErrorLevel::Error,
+            Self::UnapprovedExternalTypeRef { .. } | Self::DuplicateCrateVersions { .. } => {
+                ErrorLevel::Error
+            }
             Self::HiddenModule { .. } | Self::FieldsStripped { .. } => ErrorLevel::Warning,
         }
     }
@@ -203,20 +503,44 @@ impl ValidationError {
             Self::UnapprovedExternalTypeRef { type_name, .. }
             | Self::HiddenModule { type_name, .. }
             | Self::FieldsStripped { type_name } => type_name,
+            Self::DuplicateCrateVersions { crate_name, .. } => crate_name,
+        }
+    }
+
+    /// Returns the name of the type to highlight in the source at the error's location, if any.
+    fn highlight(&self) -> Option<&str> {
+        match self {
+            Self::UnapprovedExternalTypeRef { type_name, .. }
+            | Self::HiddenModule { type_name, .. } => Some(type_name),
+            Self::FieldsStripped { .. } | Self::DuplicateCrateVersions { .. } => None,
         }
     }
 
+    /// Returns the name of the crate that the type belongs to (the first segment of the type name).
+    ///
+    /// Types re-exported from hidden modules are only named by their re-export, so the crate
+    /// that re-exports them is returned for those instead.
+    pub fn crate_name(&self) -> &str {
+        let path = match self {
+            Self::HiddenModule { in_what_type, .. } => in_what_type,
+            _ => self.type_name(),
+        };
+        &path[0..path.find("::").unwrap_or(path.len())]
+    }
+
     pub fn location(&self) -> Option<&Span> {
         match self {
             Self::UnapprovedExternalTypeRef { location, .. }
-            | Self::HiddenModule { location, .. } => location.as_ref(),
+            | Self::HiddenModule { location, .. }
+            | Self::DuplicateCrateVersions { location, .. } => location.as_ref(),
             Self::FieldsStripped { .. } => None,
         }
     }
 
     fn sort_key(&self) -> &str {
         match self {
-            Self::UnapprovedExternalTypeRef { sort_key, .. } => sort_key.as_ref(),
+            Self::UnapprovedExternalTypeRef { sort_key, .. }
+            | Self::DuplicateCrateVersions { sort_key, .. } => sort_key.as_ref(),
             Self::FieldsStripped { type_name } | Self::HiddenModule { type_name, .. } => {
                 type_name.as_ref()
             }
@@ -248,11 +572,43 @@ impl ValidationError {
                     "Fields on `{type_name}` marked `#[doc(hidden)]` cannot be checked for external types"
                 )
             }
+            Self::DuplicateCrateVersions {
+                crate_name,
+                versions,
+                ..
+            } => {
+                write!(
+                    f,
+                    "Multiple versions of crate `{crate_name}` referenced in public API"
+                )?;
+                if !versions.is_empty() {
+                    write!(
+                        f,
+                        " (dependency graph has versions {})",
+                        versions.join(", ")
+                    )?;
+                }
+                Ok(())
+            }
         }
     }
 
     pub fn subtext(&self) -> Cow<'static, str> {
         match self {
+            Self::UnapprovedExternalTypeRef {
+                type_name,
+                what,
+                chain,
+                in_what_type,
+                ..
+            } if !chain.is_empty() => format!(
+                "in {} `{}` → {} → `{}`",
+                what,
+                in_what_type,
+                fmt_chain(chain),
+                type_name
+            )
+            .into(),
             Self::UnapprovedExternalTypeRef {
                 what, in_what_type, ..
             } => format!("in {} `{}`", what, in_what_type).into(),
@@ -260,11 +616,30 @@ impl ValidationError {
             Self::HiddenModule {
                 what, in_what_type, ..
             } => format!("in {} `{}`", what, in_what_type).into(),
+            Self::DuplicateCrateVersions {
+                crate_name, usages, ..
+            } => format!(
+                "{} refer to different versions of `{crate_name}`",
+                usages
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            )
+            .into(),
         }
     }
 }
 
-fn location_sort_key(location: Option<&Span>) -> String {
+fn fmt_chain(chain: &[TypePosition]) -> String {
+    chain
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
+
+pub(crate) fn location_sort_key(location: Option<&Span>) -> String {
     if let Some(location) = location {
         format!(
             "{}:{:07}:{:07}",
@@ -303,12 +678,289 @@ impl fmt::Display for ValidationError {
     }
 }
 
-/// Pretty printer for error context.
+/// Occurrence of a type's name in a source file.
+#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct Highlight {
+    /// Line number (starting at 1)
+    line: usize,
+    /// Character offsets into the line (starting at 0) where the name starts and ends
+    start: usize,
+    end: usize,
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns the parts of the item at `location` to search for a type's name in, in order of
+/// preference, so that the name is only underlined in the part of the item that the reference
+/// is in, rather than in its function body, other arguments, or other fields.
+///
+/// Only brackets are parsed: the generics are the first `<...>` outside of any brackets, the
+/// arguments are separated by commas in the first `(...)`, the return type follows the first
+/// `->`, and the signature ends at the first `{`, `;`, or `=` outside of any brackets.
+fn search_regions(contents: &str, location: &Span, what: Option<&ErrorLocation>) -> Vec<Span> {
+    // The item's source as characters with their positions, without line comments
+    let mut code: Vec<(char, (usize, usize))> = Vec::new();
+    let lines = contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line));
+    for (number, line) in lines.skip(location.begin.0.saturating_sub(1)) {
+        if number > location.end.0 {
+            break;
+        }
+        let chars: Vec<char> = line.chars().collect();
+        let from = if number == location.begin.0 {
+            location.begin.1
+        } else {
+            0
+        };
+        let to = if number == location.end.0 {
+            location.end.1.min(chars.len())
+        } else {
+            chars.len()
+        };
+        for column in from..to {
+            if chars[column] == '/' && chars.get(column + 1) == Some(&'/') {
+                break;
+            }
+            code.push((chars[column], (number, column)));
+        }
+    }
+    let is_where_at = |index: usize| {
+        let word: String = code.iter().skip(index).take(5).map(|(c, _)| c).collect();
+        word == "where"
+            && (index == 0 || !is_ident_char(code[index - 1].0))
+            && code
+                .get(index + 5)
+                .map_or(true, |(c, _)| !is_ident_char(*c))
+    };
+
+    let mut depth = 0usize;
+    let mut generics_begin = None;
+    let mut generics = None;
+    // Start of the argument being scanned, while in the arguments
+    let mut argument_begin = None;
+    let mut arguments = Vec::new();
+    let mut return_begin = None;
+    let mut where_begin = None;
+    let mut signature_end = code.len();
+    for (index, &(c, _)) in code.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|index| code[index].0);
+        match c {
+            '{' | ';' | '=' if depth == 0 => {
+                signature_end = index;
+                break;
+            }
+            // Generics come before the arguments and the return type
+            '<' if depth == 0
+                && generics_begin.is_none()
+                && arguments.is_empty()
+                && argument_begin.is_none()
+                && return_begin.is_none() =>
+            {
+                generics_begin = Some(index);
+                depth += 1;
+            }
+            '(' if depth == 0 && arguments.is_empty() && argument_begin.is_none() => {
+                argument_begin = Some(index + 1);
+                depth += 1;
+            }
+            ',' if depth == 1 && argument_begin.is_some() => {
+                arguments.extend(argument_begin.map(|begin| (begin, index)));
+                argument_begin = Some(index + 1);
+            }
+            '(' | '[' | '{' | '<' => depth += 1,
+            // The `>` in `->` doesn't close anything
+            '>' if previous == Some('-') => {
+                if depth == 0 && return_begin.is_none() {
+                    return_begin = Some(index + 1);
+                }
+            }
+            ')' | ']' | '}' | '>' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 && c == ')' {
+                    arguments.extend(argument_begin.take().map(|begin| (begin, index)));
+                }
+                if depth == 0 && c == '>' && generics.is_none() {
+                    generics = generics_begin.map(|begin| (begin, index + 1));
+                }
+            }
+            'w' if depth == 0 && where_begin.is_none() && is_where_at(index) => {
+                where_begin = Some(index);
+            }
+            _ => {}
+        }
+    }
+
+    let position = |index: usize| {
+        code.get(index)
+            .map_or(location.end, |&(_, position)| position)
+    };
+    let region = |(begin, end): (usize, usize)| Span {
+        filename: location.filename.clone(),
+        begin: position(begin),
+        end: position(end),
+    };
+    let signature = region((0, signature_end));
+    let clause_end = |begin: usize| {
+        where_begin
+            .filter(|&where_begin| where_begin > begin)
+            .unwrap_or(signature_end)
+    };
+    match what {
+        Some(ErrorLocation::GenericDefaultBinding | ErrorLocation::ConstGeneric) => {
+            generics.map(region).into_iter().collect()
+        }
+        // Trait bounds can also be in a where clause, or be the supertraits of a trait
+        Some(ErrorLocation::TraitBound) => generics
+            .map(region)
+            .into_iter()
+            .chain([signature])
+            .collect(),
+        // The type of an argument follows the first `:` in it, after the argument's name
+        Some(ErrorLocation::ArgumentNamed(name)) => {
+            let argument = arguments.into_iter().find_map(|(begin, end)| {
+                let colon = (begin..end).find(|&index| code[index].0 == ':')?;
+                let pattern: String = code[begin..colon].iter().map(|(c, _)| c).collect();
+                let pattern = pattern.trim();
+                let pattern = pattern.strip_prefix("mut ").unwrap_or(pattern).trim_start();
+                (pattern == name).then(|| region((colon + 1, end)))
+            });
+            vec![argument.unwrap_or(signature)]
+        }
+        Some(ErrorLocation::ReturnValue) => match return_begin {
+            Some(begin) => vec![region((begin, clause_end(begin)))],
+            None => vec![signature],
+        },
+        Some(ErrorLocation::WhereBound) => match where_begin {
+            Some(begin) => vec![region((begin, signature_end))],
+            None => vec![signature],
+        },
+        Some(ErrorLocation::ImplementedTrait | ErrorLocation::Constant | ErrorLocation::Static) => {
+            vec![signature]
+        }
+        _ => vec![location.clone()],
+    }
+}
+
+/// Finds every occurrence of the given type's name in the source within `location`.
+///
+/// The name is matched by its last segment, which is extended to cover any path written before
+/// it (such as `external_lib::` in `external_lib::SomeStruct`). Occurrences in line comments
+/// are skipped.
+fn find_highlights(contents: &str, location: &Span, type_name: &str) -> Vec<Highlight> {
+    let name: Vec<char> = type_name
+        .rsplit("::")
+        .next()
+        .unwrap_or(type_name)
+        .chars()
+        .collect();
+    if name.is_empty() {
+        return Vec::new();
+    }
+    let mut highlights = Vec::new();
+    let lines = contents
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line));
+    for (number, line) in lines.skip(location.begin.0.saturating_sub(1)) {
+        if number > location.end.0 {
+            break;
+        }
+        let chars: Vec<char> = line.chars().collect();
+        let from = if number == location.begin.0 {
+            location.begin.1
+        } else {
+            0
+        };
+        let to = if number == location.end.0 {
+            location.end.1.min(chars.len())
+        } else {
+            chars.len()
+        };
+        let comment = line.find("//").map(|index| line[..index].chars().count());
+        let mut start = from;
+        while start + name.len() <= to {
+            let end = start + name.len();
+            let bounded = (start == 0 || !is_ident_char(chars[start - 1]))
+                && (end == chars.len() || !is_ident_char(chars[end]));
+            if chars[start..end] != name[..] || !bounded {
+                start += 1;
+                continue;
+            }
+            if comment.map(|comment| comment <= start).unwrap_or(false) {
+                break;
+            }
+            // Include the path written before the name
+            let mut path_start = start;
+            while path_start >= from + 2 && chars[path_start - 2..path_start] == [':', ':'] {
+                let mut segment_start = path_start - 2;
+                while segment_start > from && is_ident_char(chars[segment_start - 1]) {
+                    segment_start -= 1;
+                }
+                if segment_start == path_start - 2 {
+                    break;
+                }
+                path_start = segment_start;
+            }
+            highlights.push(Highlight {
+                line: number,
+                start: path_start,
+                end,
+            });
+            start = end;
+        }
+    }
+    highlights
+}
+
+/// Finds the occurrences of the given type's name in the first of the [`search_regions`] for
+/// `what` that has any.
+fn find_reference_highlights(
+    contents: &str,
+    location: &Span,
+    what: Option<&ErrorLocation>,
+    type_name: &str,
+) -> Vec<Highlight> {
+    search_regions(contents, location, what)
+        .iter()
+        .map(|region| find_highlights(contents, region, type_name))
+        .find(|highlights| !highlights.is_empty())
+        .unwrap_or_default()
+}
+
+/// Joins the names of the configurations that an error was found in, writing the kind of
+/// configuration once if they all share it (such as "features `a` or `b`").
+fn fmt_run_names(names: &[String]) -> String {
+    for prefix in ["features ", "target "] {
+        let rest: Option<Vec<&str>> = names
+            .iter()
+            .map(|name| {
+                name.strip_prefix(prefix)
+                    .filter(|rest| !rest.contains(" on "))
+            })
+            .collect();
+        if let Some(rest) = rest {
+            return format!("{prefix}{}", rest.join(" or "));
+        }
+    }
+    names.join(" or ")
+}
+
+/// Pretty printer for validation errors.
 ///
 /// This makes validation errors look similar to the compiler errors from rustc.
 pub struct ErrorPrinter {
     workspace_root: PathBuf,
-    file_cache: HashMap<PathBuf, String>,
+    /// Contents of each source file, or the reason it couldn't be loaded
+    file_cache: HashMap<PathBuf, Result<String, String>>,
+    group_by_type: bool,
+    color: ColorChoice,
+    stream: OutputStream,
+    /// Config the errors were found with, which is used to suggest how to allow types
+    config: Config,
 }
 
 impl ErrorPrinter {
@@ -316,130 +968,479 @@ impl ErrorPrinter {
         Self {
             workspace_root: workspace_root.into(),
             file_cache: HashMap::new(),
+            group_by_type: false,
+            color: ColorChoice::Auto,
+            stream: OutputStream::Stdout,
+            config: Config::default(),
         }
     }
 
-    fn get_file_contents(&mut self, path: &Path) -> Result<&str> {
+    /// Sets whether to report each unapproved external type once, along with every place
+    /// that references it, rather than once per reference. Defaults to `false`.
+    pub fn with_group_by_type(mut self, group_by_type: bool) -> Self {
+        self.group_by_type = group_by_type;
+        self
+    }
+
+    /// Sets when to color the output. Defaults to [`ColorChoice::Auto`].
+    pub fn with_color(mut self, color: ColorChoice) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the stream to print to. Defaults to [`OutputStream::Stdout`].
+    pub fn with_stream(mut self, stream: OutputStream) -> Self {
+        self.stream = stream;
+        self
+    }
+
+    /// Sets the config the errors were found with, so that suggestions for allowing external
+    /// types can point out similar globs that are already in the config.
+    pub fn with_config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn load_source(&mut self, path: &Path) -> Result<&str, &str> {
         if !self.file_cache.contains_key(path) {
-            let full_file_name = self.workspace_root.join(path).canonicalize()?;
+            let full_file_name = self.workspace_root.join(path);
             let contents = std::fs::read_to_string(&full_file_name)
-                .context("failed to load source file for error context")
-                .context(full_file_name.to_string_lossy().to_string())?;
+                .map_err(|err| format!("failed to read {full_file_name:?}: {err}"));
             self.file_cache.insert(path.to_path_buf(), contents);
         }
-        Ok(self.file_cache.get(path).unwrap())
+        self.file_cache[path].as_deref().map_err(String::as_str)
     }
 
-    fn print_error_level(level: ErrorLevel) {
-        use owo_colors::{OwoColorize, Stream};
-        match level {
-            ErrorLevel::Error => {
-                print!(
-                    "{}",
-                    "error: "
-                        .if_supports_color(Stream::Stdout, |text| text.red())
-                        .if_supports_color(Stream::Stdout, |text| text.bold())
-                );
+    /// Returns a snippet of the source at the location of the given `references`, which must
+    /// all be at the same location.
+    ///
+    /// Every occurrence of the referenced type's name within the part of the location that each
+    /// reference is in (see [`search_regions`]) is underlined. If there aren't any (for example,
+    /// because the type was imported under another name), then the whole location is underlined
+    /// instead.
+    fn snippet(&mut self, location: &Span, references: &[&ValidationError]) -> Snippet {
+        let references: Vec<(Option<&ErrorLocation>, &str)> = references
+            .iter()
+            .filter_map(|reference| Some((reference.what(), reference.highlight()?)))
+            .collect();
+        self.labels(location, &references, Label::primary)
+            .into_iter()
+            .fold(Snippet::new(&location.filename), Snippet::with_label)
+    }
+
+    /// Returns a label for every occurrence of each referenced type's name within the part of
+    /// the location that the reference is in, or a label for the whole location if there
+    /// aren't any. The references are given as where in the item they are and the type's name.
+    fn labels(
+        &mut self,
+        location: &Span,
+        references: &[(Option<&ErrorLocation>, &str)],
+        label: fn((usize, usize), (usize, usize)) -> Label,
+    ) -> Vec<Label> {
+        let mut highlights = Vec::new();
+        if let Ok(contents) = self.load_source(&location.filename) {
+            for &(what, type_name) in references {
+                highlights.extend(find_reference_highlights(
+                    contents, location, what, type_name,
+                ));
             }
-            ErrorLevel::Warning => {
-                print!(
-                    "{}",
-                    "warning: "
-                        .if_supports_color(Stream::Stdout, |text| text.yellow())
-                        .if_supports_color(Stream::Stdout, |text| text.bold())
-                );
+        }
+        highlights.sort();
+        highlights.dedup();
+        if highlights.is_empty() {
+            return vec![label(location.begin, location.end)];
+        }
+        highlights
+            .into_iter()
+            .map(|highlight| {
+                label(
+                    (highlight.line, highlight.start),
+                    (highlight.line, highlight.end),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns a snippet for each usage in a [`ValidationError::DuplicateCrateVersions`] error,
+    /// with the referenced type labeled with the version of the crate it's from. The first
+    /// usage is the primary one.
+    fn usage_snippets(&mut self, usages: &[CrateUsage]) -> Vec<Snippet> {
+        let mut snippets = Vec::new();
+        for (index, usage) in usages.iter().enumerate() {
+            let location = match &usage.location {
+                Some(location) => location,
+                None => continue,
+            };
+            let label = if index == 0 {
+                Label::primary
+            } else {
+                Label::secondary
+            };
+            let mut labels = self.labels(location, &[(Some(&usage.what), &usage.type_name)], label);
+            if let Some(version) = &usage.version {
+                labels[0] = labels[0].clone().with_text(version);
             }
+            snippets.push(
+                labels
+                    .into_iter()
+                    .fold(Snippet::new(&location.filename), Snippet::with_label),
+            );
         }
+        snippets
     }
 
-    /// Outputs a human readable error with file location context
+    /// Returns the diagnostic for a single error.
     ///
     /// # Example output
     ///
     /// ```text
-    /// error: Unapproved external type `external_lib::SomeStruct` referenced in public API
-    ///    --> test-crate/src/lib.rs:38:1
+    /// error[CET0001]: Unapproved external type `external_lib::SomeStruct` referenced in public API
+    ///   --> test-crate/src/lib.rs:38:36
     ///    |
     /// 38 | pub fn external_in_fn_input(_one: &SomeStruct, _two: impl SimpleTrait) {}
-    ///    | ^-----------------------------------------------------------------------^
+    ///    |                                    ^^^^^^^^^^
     ///    |
     ///    = in argument named `_one` of `test_crate::external_in_fn_input`
+    ///    = help: to allow this type, add `"external_lib::SomeStruct"` to `allowed_external_types` in the config file
+    ///    = help: or allow every type from `external_lib` with `"external_lib::*"`
     /// ```
-    pub fn pretty_print_error_context(&mut self, location: &Span, subtext: &str) {
-        match self.get_file_contents(&location.filename) {
-            Ok(file_contents) => {
-                let begin = Self::position_from_line_col(file_contents, location.begin);
-                let end = Self::position_from_line_col(file_contents, location.end);
-
-                // HACK: Using Pest to do the pretty error context formatting for lack of
-                // knowledge of a smaller library tailored to this use-case
-                let variant = pest::error::ErrorVariant::<()>::CustomError {
-                    message: subtext.into(),
-                };
-                let err_context = match (begin, end) {
-                    (Some(b), Some(e)) => {
-                        Some(pest::error::Error::new_from_span(variant, b.span(&e)))
-                    }
-                    (Some(b), None) => Some(pest::error::Error::new_from_pos(variant, b)),
-                    _ => None,
-                };
-                if let Some(err_context) = err_context {
-                    println!(
-                        "{}\n",
-                        err_context.with_path(&location.filename.to_string_lossy())
-                    );
-                }
+    pub fn diagnostic(&mut self, errors: &ValidationErrors, error: &ValidationError) -> Diagnostic {
+        let mut diagnostic =
+            Diagnostic::new(error.level(), error.to_string()).with_code(error.code().as_str());
+        if let ValidationError::DuplicateCrateVersions { usages, .. } = error {
+            let mut snippets = self.usage_snippets(usages);
+            // The annotation that lists every usage follows all of their snippets
+            if let Some(last) = snippets.pop() {
+                snippets.push(last.with_annotation(error.subtext()));
             }
-            Err(err) => {
-                Self::print_error_level(ErrorLevel::Error);
-                println!("{subtext}");
-                println!(
-                    "  --> {}:{}:{}",
-                    location.filename.to_string_lossy(),
-                    location.begin.0,
-                    location.begin.1 + 1
+            diagnostic = snippets
+                .into_iter()
+                .fold(diagnostic, Diagnostic::with_snippet);
+        } else if let Some(location) = error.location() {
+            let snippet = self
+                .snippet(location, &[error])
+                .with_annotation(error.subtext());
+            diagnostic = diagnostic.with_snippet(snippet);
+        }
+        let diagnostic = Self::notes(errors, error)
+            .into_iter()
+            .fold(diagnostic, Diagnostic::with_note);
+        self.helps(error)
+            .into_iter()
+            .fold(diagnostic, Diagnostic::with_help)
+    }
+
+    /// Returns a diagnostic with every reference to a single unapproved external type, with the
+    /// references at each location listed under a single snippet.
+    fn type_references_diagnostic(
+        &mut self,
+        errors: &ValidationErrors,
+        references: &[&ValidationError],
+    ) -> Diagnostic {
+        let message = match references.len() {
+            1 => references[0].to_string(),
+            count => format!("{} ({count} references)", references[0]),
+        };
+        let mut diagnostic = Diagnostic::new(ErrorLevel::Error, message)
+            .with_code(ErrorCode::UnapprovedExternalType.as_str());
+        let mut remaining = references;
+        while let Some(first) = remaining.first() {
+            // References are sorted by location, so references at the same location are adjacent
+            let count = remaining
+                .iter()
+                .take_while(|reference| reference.location() == first.location())
+                .count();
+            let subtexts = remaining[..count].iter().map(|reference| {
+                let mut subtext = reference.subtext().to_string();
+                for note in Self::notes(errors, reference) {
+                    subtext.push_str(&format!(" ({note})"));
+                }
+                subtext
+            });
+            if let Some(location) = first.location() {
+                let snippet = subtexts.fold(
+                    self.snippet(location, &remaining[..count]),
+                    Snippet::with_annotation,
                 );
-                println!("   | Failed to load {:?}", location.filename);
-                println!("   | relative to {:?}", self.workspace_root);
-                println!("   | to provide error message context.");
-                println!("   | Cause: {err:?}");
+                diagnostic = diagnostic.with_snippet(snippet);
+            } else {
+                diagnostic = subtexts.fold(diagnostic, Diagnostic::with_note);
             }
+            remaining = &remaining[count..];
         }
+        self.helps(references[0])
+            .into_iter()
+            .fold(diagnostic, Diagnostic::with_help)
     }
 
-    fn position_from_line_col(contents: &str, (line, col): (usize, usize)) -> Option<Position> {
-        let (mut cl, mut cc) = (1, 1);
-        let content_bytes = contents.as_bytes();
-        for (index, &byte) in content_bytes.iter().enumerate() {
-            if cl == line && cc == col {
-                return Position::new(contents, index);
-            }
+    fn notes(errors: &ValidationErrors, error: &ValidationError) -> Vec<String> {
+        let mut notes = Vec::new();
+        if !error.other_paths().is_empty() {
+            let paths: Vec<String> = error
+                .other_paths()
+                .iter()
+                .map(|path| format!("`{path}`"))
+                .collect();
+            notes.push(format!("also reachable as {}", paths.join(", ")));
+        }
+        if let Some(names) = errors.found_only_in(error) {
+            notes.push(format!("only found with {}", fmt_run_names(names)));
+        }
+        notes
+    }
 
-            cc += 1;
-            if byte == b'\n' {
-                cl += 1;
-                cc = 0;
-            }
+    /// Returns suggestions for changes to the config that would allow the error's type: the
+    /// narrowest glob, the crate-wide glob, and a correction to a similar glob in the config.
+    fn helps(&self, error: &ValidationError) -> Vec<String> {
+        let type_name = match error {
+            ValidationError::UnapprovedExternalTypeRef { type_name, .. } => type_name,
+            _ => return Vec::new(),
+        };
+        let mut helps = vec![format!(
+            "to allow this type, add `\"{type_name}\"` to `allowed_external_types` in the config file"
+        )];
+        if let Some((crate_name, _)) = type_name.split_once("::") {
+            helps.push(format!(
+                "or allow every type from `{crate_name}` with `\"{crate_name}::*\"`"
+            ));
         }
-        None
+        if let Some((glob, suggestion)) = self.config.similar_allowed_glob(type_name) {
+            helps.push(format!(
+                "did you mean `\"{suggestion}\"` instead of `\"{glob}\"` in `allowed_external_types`?"
+            ));
+        }
+        helps
+    }
+
+    /// Returns a diagnostic from [`ErrorPrinter::diagnostic`] rendered the same way as when it's
+    /// printed, but without color.
+    pub fn render_plain(&self, diagnostic: &Diagnostic) -> String {
+        Renderer::new(false).render(diagnostic, &self.file_cache)
     }
 
     pub fn pretty_print_errors(&mut self, errors: &ValidationErrors) {
+        let color = self.color.enabled(self.stream);
+        let renderer = Renderer::new(color);
+        let mut printed_types = HashSet::new();
         for error in errors.iter() {
-            Self::print_error_level(error.level());
-            println!("{}", error);
-            if let Some(location) = error.location() {
-                self.pretty_print_error_context(location, error.subtext().as_ref())
-            }
+            let diagnostic = match error {
+                ValidationError::UnapprovedExternalTypeRef { type_name, .. }
+                    if self.group_by_type =>
+                {
+                    // Each type is reported along with all of its references the first time
+                    // that it's found
+                    if !printed_types.insert(type_name) {
+                        continue;
+                    }
+                    let references: Vec<&ValidationError> = errors
+                        .iter()
+                        .filter(|other| {
+                            matches!(other, ValidationError::UnapprovedExternalTypeRef { .. })
+                                && other.type_name() == type_name
+                        })
+                        .collect();
+                    self.type_references_diagnostic(errors, &references)
+                }
+                _ => self.diagnostic(errors, error),
+            };
+            self.stream
+                .print(&renderer.render(&diagnostic, &self.file_cache));
         }
         if !errors.is_empty() {
-            use owo_colors::{OwoColorize, Stream};
+            use owo_colors::OwoColorize;
             let (error_count, warning_count) = (errors.error_count(), errors.warning_count());
-            println!(
-                "{error_count} {errors}, {warning_count} {warnings} emitted",
-                errors = "errors".if_supports_color(Stream::Stdout, |text| text.red()),
-                warnings = "warnings".if_supports_color(Stream::Stdout, |text| text.yellow())
-            );
+            let (errors, warnings) = if color {
+                ("errors".red().to_string(), "warnings".yellow().to_string())
+            } else {
+                ("errors".to_string(), "warnings".to_string())
+            };
+            self.stream.print(&format!(
+                "{error_count} {errors}, {warning_count} {warnings} emitted\n"
+            ));
+        }
+        // Like rustc, point out how to find out more about the errors that were found
+        let codes: BTreeSet<ErrorCode> = errors.iter().map(ValidationError::code).collect();
+        let explain = "cargo check-external-types --explain";
+        match codes.iter().collect::<Vec<_>>()[..] {
+            [] => {}
+            [code] => self.stream.print(&format!(
+                "For more information about this error, try `{explain} {code}`.\n"
+            )),
+            [first, ..] => {
+                let codes: Vec<&str> = codes.iter().map(|code| code.as_str()).collect();
+                self.stream.print(&format!(
+                    "Some errors have detailed explanations: {}.\n\
+                    For more information about an error, try `{explain} {first}`.\n",
+                    codes.join(", ")
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        find_highlights, find_reference_highlights, fmt_run_names, ErrorLocation, ErrorPrinter,
+        Highlight, ValidationError,
+    };
+    use crate::config::Config;
+    use rustdoc_types::Span;
+    use wildmatch::WildMatch;
+
+    fn span(begin: (usize, usize), end: (usize, usize)) -> Span {
+        Span {
+            filename: "lib.rs".into(),
+            begin,
+            end,
         }
     }
+
+    fn highlight(line: usize, start: usize, end: usize) -> Highlight {
+        Highlight { line, start, end }
+    }
+
+    #[test]
+    fn highlights_each_occurrence() {
+        let contents = "pub struct Other;\n\
+            pub fn f(a: &Thing, b: Option<ext::Thing>) -> Things {}\n";
+        assert_eq!(
+            vec![highlight(2, 13, 18), highlight(2, 30, 40)],
+            find_highlights(contents, &span((2, 0), (2, 55)), "ext::Thing")
+        );
+        // Only the location is searched
+        assert_eq!(
+            vec![highlight(2, 30, 40)],
+            find_highlights(contents, &span((2, 20), (2, 55)), "ext::Thing")
+        );
+        assert_eq!(
+            Vec::<Highlight>::new(),
+            find_highlights(contents, &span((2, 0), (2, 55)), "ext::Other")
+        );
+    }
+
+    #[test]
+    fn skips_comments() {
+        let contents = "pub struct S {\n    // Thing\n    pub a: Thing, // Thing\n}\n";
+        assert_eq!(
+            vec![highlight(3, 11, 16)],
+            find_highlights(contents, &span((1, 0), (4, 1)), "ext::Thing")
+        );
+    }
+
+    #[test]
+    fn highlights_only_the_part_of_the_item_with_the_reference() {
+        let contents = "pub struct S<T = ext::Thing> {\n    \
+            pub a: ext::Thing,\n    \
+            pub b: Option<ext::Thing>,\n\
+            }\n\
+            pub fn f<T: ext::Trait>(a: ext::Thing, mut b: ext::Thing) -> ext::Thing where T: ext::Trait {\n    \
+            let _: ext::Thing = ext::Thing;\n    \
+            ext::Thing\n\
+            }\n";
+        let find = |location, what, type_name| {
+            find_reference_highlights(contents, &location, Some(&what), type_name)
+        };
+        // Neither the fields nor the function body, nor the other arguments
+        assert_eq!(
+            vec![highlight(1, 17, 27)],
+            find(
+                span((1, 0), (4, 1)),
+                ErrorLocation::GenericDefaultBinding,
+                "ext::Thing"
+            )
+        );
+        assert_eq!(
+            vec![highlight(2, 11, 21)],
+            find(
+                span((2, 4), (2, 21)),
+                ErrorLocation::StructField,
+                "ext::Thing"
+            )
+        );
+        assert_eq!(
+            vec![highlight(5, 27, 37)],
+            find(
+                span((5, 0), (8, 1)),
+                ErrorLocation::ArgumentNamed("a".into()),
+                "ext::Thing"
+            )
+        );
+        assert_eq!(
+            vec![highlight(5, 46, 56)],
+            find(
+                span((5, 0), (8, 1)),
+                ErrorLocation::ArgumentNamed("b".into()),
+                "ext::Thing"
+            )
+        );
+        assert_eq!(
+            vec![highlight(5, 61, 71)],
+            find(
+                span((5, 0), (8, 1)),
+                ErrorLocation::ReturnValue,
+                "ext::Thing"
+            )
+        );
+        assert_eq!(
+            vec![highlight(5, 81, 91)],
+            find(
+                span((5, 0), (8, 1)),
+                ErrorLocation::WhereBound,
+                "ext::Trait"
+            )
+        );
+        assert_eq!(
+            vec![highlight(5, 12, 22)],
+            find(
+                span((5, 0), (8, 1)),
+                ErrorLocation::TraitBound,
+                "ext::Trait"
+            )
+        );
+    }
+
+    #[test]
+    fn run_names() {
+        let names =
+            |names: &[&str]| fmt_run_names(&names.iter().map(|&n| n.into()).collect::<Vec<_>>());
+        assert_eq!("features `a`", names(&["features `a`"]));
+        assert_eq!(
+            "features `default` or `simple`",
+            names(&["features `default`", "features `simple`"])
+        );
+        assert_eq!("target `a` or `b`", names(&["target `a`", "target `b`"]));
+        assert_eq!(
+            "no features or features `a`",
+            names(&["no features", "features `a`"])
+        );
+        assert_eq!(
+            "features `a` on target `x` or features `b` on target `x`",
+            names(&["features `a` on target `x`", "features `b` on target `x`"])
+        );
+    }
+
+    #[test]
+    fn suggests_config_changes() {
+        let config = Config {
+            allowed_external_types: vec![WildMatch::new("extrenal_lib::*")],
+            ..Default::default()
+        };
+        let printer = ErrorPrinter::new("/").with_config(config);
+        let error = ValidationError::unapproved_external_type_ref(
+            "external_lib::Thing",
+            &ErrorLocation::ReturnValue,
+            &[],
+            "test_crate::f",
+            None,
+        );
+        assert_eq!(
+            vec![
+                "to allow this type, add `\"external_lib::Thing\"` to `allowed_external_types` in the config file",
+                "or allow every type from `external_lib` with `\"external_lib::*\"`",
+                "did you mean `\"external_lib::*\"` instead of `\"extrenal_lib::*\"` in `allowed_external_types`?",
+            ],
+            printer.helps(&error)
+        );
+    }
 }
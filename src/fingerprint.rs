@@ -0,0 +1,201 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::here;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Hashes bytes with 64-bit FNV-1a.
+///
+/// Unlike the standard library's `DefaultHasher`, whose algorithm can change between Rust
+/// releases, this gives the same hash no matter which compiler this tool was built with, so
+/// it can be used for hashes that are stored between runs.
+pub fn stable_hash(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// Record of every input that affects rustdoc's JSON output, used to tell whether
+/// previously generated output can be reused.
+///
+/// The fingerprint is stored as a text file with one `key = value` line per input so that
+/// it's easy to see why output was regenerated. File contents are hashed with [`stable_hash`],
+/// so the hashes are the same for every build of this tool.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Fingerprint {
+    entries: BTreeMap<String, String>,
+}
+
+impl Fingerprint {
+    pub fn new() -> Self {
+        let mut fingerprint = Self::default();
+        fingerprint.add("tool-version", env!("CARGO_PKG_VERSION"));
+        fingerprint
+    }
+
+    /// Adds an input with the given value.
+    pub fn add(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.entries.insert(key.into(), value.into());
+    }
+
+    /// Adds the hash of a file, or of every file in a directory. Directories named `target`,
+    /// hidden files and directories, and the paths in `skip` are left out.
+    pub fn add_path(&mut self, path: &Path, skip: &[PathBuf]) -> Result<()> {
+        if skip.iter().any(|skipped| skipped == path) {
+            return Ok(());
+        }
+        if path.is_dir() {
+            let mut entries = fs::read_dir(path)
+                .context(here!("failed to read {path:?}"))?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()
+                .context(here!())?;
+            entries.sort();
+            for entry in entries {
+                let name = entry.file_name().unwrap_or_default().to_string_lossy();
+                let skipped_dir = entry.is_dir() && name == "target";
+                if !name.starts_with('.') && !skipped_dir {
+                    self.add_path(&entry, skip)?;
+                }
+            }
+        } else if path.exists() {
+            let contents = fs::read(path).context(here!("failed to read {path:?}"))?;
+            self.add(
+                path.display().to_string(),
+                format!("{:016x}", stable_hash(&contents)),
+            );
+        }
+        Ok(())
+    }
+
+    /// Adds the size and modification time of a generated output file, so that the output is
+    /// regenerated if something else overwrites it.
+    pub fn add_output(&mut self, path: &Path) -> Result<()> {
+        self.add("output", output_stamp(path)?);
+        Ok(())
+    }
+
+    /// Returns true if the fingerprint stored at `path` is identical to this one, and the
+    /// output it was stored for hasn't changed since.
+    pub fn matches_stored(&self, path: &Path, output_path: &Path) -> bool {
+        let (Ok(stored), Ok(output)) = (fs::read_to_string(path), output_stamp(output_path)) else {
+            return false;
+        };
+        let mut expected = self.clone();
+        expected.add("output", output);
+        stored == expected.to_string()
+    }
+
+    /// Stores this fingerprint at the given path.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        fs::write(path, self.to_string()).context(here!("failed to write {path:?}"))
+    }
+}
+
+fn output_stamp(path: &Path) -> Result<String> {
+    let metadata = fs::metadata(path).context(here!("failed to read {path:?}"))?;
+    let modified = metadata
+        .modified()
+        .context(here!())?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(format!(
+        "{} bytes, modified {}",
+        metadata.len(),
+        modified.as_nanos()
+    ))
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in &self.entries {
+            writeln!(f, "{key} = {value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stable_hash, Fingerprint};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join("check-external-types-fingerprint-test")
+            .join(format!("{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        dir
+    }
+
+    fn fingerprint(dir: &Path, skip: &[PathBuf]) -> Fingerprint {
+        let mut fingerprint = Fingerprint::new();
+        fingerprint.add_path(dir, skip).unwrap();
+        fingerprint
+    }
+
+    #[test]
+    fn stable_hash_is_fnv1a() {
+        // Test vectors from the FNV reference implementation
+        assert_eq!(0xcbf2_9ce4_8422_2325, stable_hash(b""));
+        assert_eq!(0xaf63_dc4c_8601_ec8c, stable_hash(b"a"));
+        assert_eq!(0x8594_4171_f739_67e8, stable_hash(b"foobar"));
+    }
+
+    #[test]
+    fn changes_with_sources() {
+        let dir = test_dir("sources");
+        fs::write(dir.join("src/lib.rs"), "pub struct A;").unwrap();
+        let before = fingerprint(&dir, &[]);
+        assert_eq!(before, fingerprint(&dir, &[]));
+
+        fs::write(dir.join("src/lib.rs"), "pub struct B;").unwrap();
+        assert_ne!(before, fingerprint(&dir, &[]));
+    }
+
+    #[test]
+    fn ignores_generated_files() {
+        let dir = test_dir("generated");
+        fs::write(dir.join("src/lib.rs"), "pub struct A;").unwrap();
+        let before = fingerprint(&dir, &[dir.join("out")]);
+
+        fs::write(dir.join("target/lib.json"), "{}").unwrap();
+        fs::write(dir.join(".hidden"), "").unwrap();
+        fs::create_dir_all(dir.join("out")).unwrap();
+        fs::write(dir.join("out/lib.json"), "{}").unwrap();
+        assert_eq!(before, fingerprint(&dir, &[dir.join("out")]));
+    }
+
+    #[test]
+    fn matches_stored() {
+        let dir = test_dir("stored");
+        let output = dir.join("target/lib.json");
+        let stored = dir.join("target/lib.fingerprint");
+        fs::write(&output, "{}").unwrap();
+
+        let mut fingerprint = Fingerprint::new();
+        fingerprint.add("features", "a,b");
+        assert!(!fingerprint.matches_stored(&stored, &output));
+
+        let mut with_output = fingerprint.clone();
+        with_output.add_output(&output).unwrap();
+        with_output.write(&stored).unwrap();
+        assert!(fingerprint.matches_stored(&stored, &output));
+
+        let mut other = Fingerprint::new();
+        other.add("features", "a");
+        assert!(!other.matches_stored(&stored, &output));
+    }
+}
@@ -0,0 +1,367 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::error::{ErrorLevel, ErrorPrinter, ValidationErrors};
+use crate::report::Summary;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+const STYLE: &str = include_str!("html/report.css");
+const SCRIPT: &str = include_str!("html/report.js");
+
+/// Escapes text for use in HTML element contents and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// One error or warning in an [`HtmlReport`].
+#[derive(Debug)]
+struct Finding {
+    crate_name: String,
+    type_name: String,
+    level: ErrorLevel,
+    /// Kind of place in the item that the type was found in
+    location: String,
+    item: String,
+    /// File position of the finding, if it has one
+    used_in: Option<String>,
+    /// The finding rendered like in the `errors` output format, including its source snippet
+    rendered: String,
+}
+
+/// Errors and warning counts for one crate in an [`HtmlReport`].
+#[derive(Debug, Default)]
+struct CrateSummary {
+    errors: usize,
+    warnings: usize,
+    types: usize,
+}
+
+/// Self-contained HTML page with a searchable and filterable table of every error and warning,
+/// with collapsible source snippets, a summary of each crate, and links between the findings
+/// that refer to the same type.
+///
+/// This is intended as a reading artifact for API reviews.
+#[derive(Debug)]
+pub struct HtmlReport {
+    title: String,
+    findings: Vec<Finding>,
+    summary: Option<Summary>,
+}
+
+impl HtmlReport {
+    /// Creates a report of the given `errors`, with source snippets loaded by the `printer`.
+    pub fn new(
+        title: impl Into<String>,
+        errors: &ValidationErrors,
+        printer: &mut ErrorPrinter,
+    ) -> Self {
+        let findings = errors
+            .iter()
+            .map(|error| {
+                let diagnostic = printer.diagnostic(errors, error);
+                Finding {
+                    crate_name: error.crate_name().into(),
+                    type_name: error.type_name().into(),
+                    level: error.level(),
+                    location: error
+                        .what()
+                        .map(|what| what.kind())
+                        .unwrap_or_default()
+                        .into(),
+                    item: error.in_what_type().unwrap_or_default().into(),
+                    // The same position as the snippet's, which is where the type is rather than
+                    // where the item starts
+                    used_in: diagnostic.position(),
+                    rendered: printer.render_plain(&diagnostic).trim_end().into(),
+                }
+            })
+            .collect();
+        Self {
+            title: title.into(),
+            findings,
+            summary: None,
+        }
+    }
+
+    /// Includes the tables of the given [`Summary`] in the report, after the crate summaries.
+    pub fn with_summary(mut self, summary: Summary) -> Self {
+        self.summary = Some(summary);
+        self
+    }
+
+    /// Returns the indices of the findings that refer to each type, keyed by type name.
+    fn findings_by_type(&self) -> BTreeMap<&str, Vec<usize>> {
+        let mut by_type: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+        for (index, finding) in self.findings.iter().enumerate() {
+            by_type.entry(&finding.type_name).or_default().push(index);
+        }
+        by_type
+    }
+
+    fn fmt_crate_summaries(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut summaries: BTreeMap<&str, CrateSummary> = BTreeMap::new();
+        for indices in self.findings_by_type().into_values() {
+            let crate_name = self.findings[indices[0]].crate_name.as_str();
+            let summary = summaries.entry(crate_name).or_default();
+            summary.types += 1;
+            for index in indices {
+                match self.findings[index].level {
+                    ErrorLevel::Error => summary.errors += 1,
+                    ErrorLevel::Warning => summary.warnings += 1,
+                }
+            }
+        }
+        writeln!(f, "<h2>Crates</h2>")?;
+        writeln!(f, "<table id=\"crates\">")?;
+        writeln!(
+            f,
+            "<thead><tr><th>Crate</th><th>Errors</th><th>Warnings</th><th>Types</th></tr></thead>"
+        )?;
+        writeln!(f, "<tbody>")?;
+        for (crate_name, summary) in summaries {
+            let crate_name = escape(crate_name);
+            writeln!(
+                f,
+                "<tr><td><a href=\"#findings\" data-filter-crate=\"{crate_name}\">{crate_name}</a></td>\
+                <td>{}</td><td>{}</td><td>{}</td></tr>",
+                summary.errors, summary.warnings, summary.types
+            )?;
+        }
+        writeln!(f, "</tbody>")?;
+        writeln!(f, "</table>")
+    }
+
+    fn fmt_summary(&self, f: &mut fmt::Formatter<'_>, summary: &Summary) -> fmt::Result {
+        writeln!(f, "<h2>Summary</h2>")?;
+        writeln!(
+            f,
+            "<p>Unapproved external type references: {}</p>",
+            summary.total()
+        )?;
+        let tables = [
+            ("External Crate", summary.by_crate()),
+            ("Location", summary.by_location()),
+            ("Module", summary.by_module()),
+            ("Most Referenced Type", summary.top_types()),
+        ];
+        for (heading, counts) in tables {
+            writeln!(f, "<table class=\"summary\">")?;
+            writeln!(
+                f,
+                "<thead><tr><th>{heading}</th><th>References</th></tr></thead>"
+            )?;
+            writeln!(f, "<tbody>")?;
+            for (name, count) in counts {
+                writeln!(f, "<tr><td>{}</td><td>{count}</td></tr>", escape(name))?;
+            }
+            writeln!(f, "</tbody>")?;
+            writeln!(f, "</table>")?;
+        }
+        Ok(())
+    }
+
+    fn fmt_filters(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let crate_names: BTreeSet<&str> = self
+            .findings
+            .iter()
+            .map(|finding| finding.crate_name.as_str())
+            .collect();
+        writeln!(f, "<div class=\"filters\">")?;
+        writeln!(
+            f,
+            "<input id=\"search\" type=\"search\" placeholder=\"Search findings\">"
+        )?;
+        writeln!(f, "<select id=\"level\">")?;
+        writeln!(f, "<option value=\"\">All levels</option>")?;
+        writeln!(f, "<option value=\"error\">Errors</option>")?;
+        writeln!(f, "<option value=\"warning\">Warnings</option>")?;
+        writeln!(f, "</select>")?;
+        writeln!(f, "<select id=\"crate\">")?;
+        writeln!(f, "<option value=\"\">All crates</option>")?;
+        for crate_name in crate_names {
+            let crate_name = escape(crate_name);
+            writeln!(f, "<option value=\"{crate_name}\">{crate_name}</option>")?;
+        }
+        writeln!(f, "</select>")?;
+        writeln!(
+            f,
+            "<span>Showing <span id=\"shown\">{}</span> of {}</span>",
+            self.findings.len(),
+            self.findings.len()
+        )?;
+        writeln!(f, "</div>")
+    }
+
+    fn fmt_findings(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        type_ids: &BTreeMap<&str, usize>,
+    ) -> fmt::Result {
+        writeln!(f, "<table id=\"findings\">")?;
+        writeln!(
+            f,
+            "<thead><tr><th>Crate</th><th>Type</th><th>Level</th><th>Location</th><th>Item</th>\
+            <th>Used In</th></tr></thead>"
+        )?;
+        writeln!(f, "<tbody>")?;
+        for (index, finding) in self.findings.iter().enumerate() {
+            let crate_name = escape(&finding.crate_name);
+            let level = finding.level;
+            // The search matches the crate, type, location, and item, but not the text of the snippet
+            let search = escape(
+                &[
+                    finding.crate_name.as_str(),
+                    &finding.type_name,
+                    &finding.location,
+                    &finding.item,
+                ]
+                .join(" ")
+                .to_lowercase(),
+            );
+            writeln!(
+                f,
+                "<tr id=\"finding-{index}\" data-crate=\"{crate_name}\" data-level=\"{level}\" \
+                data-search=\"{search}\">"
+            )?;
+            writeln!(f, "<td>{crate_name}</td>")?;
+            writeln!(
+                f,
+                "<td><a href=\"#type-{}\"><code>{}</code></a></td>",
+                type_ids[finding.type_name.as_str()],
+                escape(&finding.type_name)
+            )?;
+            writeln!(f, "<td class=\"level-{level}\">{level}</td>")?;
+            writeln!(f, "<td>{}</td>", escape(&finding.location))?;
+            writeln!(f, "<td><code>{}</code></td>", escape(&finding.item))?;
+            writeln!(
+                f,
+                "<td><details><summary>{}</summary><pre>{}</pre></details></td>",
+                escape(finding.used_in.as_deref().unwrap_or("details")),
+                escape(&finding.rendered)
+            )?;
+            writeln!(f, "</tr>")?;
+        }
+        writeln!(f, "</tbody>")?;
+        writeln!(f, "</table>")
+    }
+
+    fn fmt_types(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        findings_by_type: &BTreeMap<&str, Vec<usize>>,
+        type_ids: &BTreeMap<&str, usize>,
+    ) -> fmt::Result {
+        writeln!(f, "<h2>Types</h2>")?;
+        for (type_name, indices) in findings_by_type {
+            writeln!(
+                f,
+                "<h3 id=\"type-{}\"><code>{}</code> ({} {})</h3>",
+                type_ids[type_name],
+                escape(type_name),
+                indices.len(),
+                if indices.len() == 1 {
+                    "finding"
+                } else {
+                    "findings"
+                }
+            )?;
+            writeln!(f, "<ul>")?;
+            for &index in indices {
+                let finding = &self.findings[index];
+                let location = match finding.location.as_str() {
+                    "" => String::new(),
+                    location => format!("{location} of "),
+                };
+                writeln!(
+                    f,
+                    "<li><a href=\"#finding-{index}\">{}<code>{}</code></a></li>",
+                    escape(&location),
+                    escape(&finding.item)
+                )?;
+            }
+            writeln!(f, "</ul>")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for HtmlReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let findings_by_type = self.findings_by_type();
+        let type_ids: BTreeMap<&str, usize> = findings_by_type
+            .keys()
+            .enumerate()
+            .map(|(id, &type_name)| (type_name, id))
+            .collect();
+        let title = escape(&self.title);
+        writeln!(f, "<!DOCTYPE html>")?;
+        writeln!(f, "<html lang=\"en\">")?;
+        writeln!(f, "<head>")?;
+        writeln!(f, "<meta charset=\"utf-8\">")?;
+        writeln!(f, "<title>{title}</title>")?;
+        write!(f, "<style>\n{STYLE}</style>\n")?;
+        writeln!(f, "</head>")?;
+        writeln!(f, "<body>")?;
+        writeln!(f, "<h1>{title}</h1>")?;
+        self.fmt_crate_summaries(f)?;
+        if let Some(summary) = &self.summary {
+            self.fmt_summary(f, summary)?;
+        }
+        writeln!(f, "<h2>Findings</h2>")?;
+        self.fmt_filters(f)?;
+        self.fmt_findings(f, &type_ids)?;
+        self.fmt_types(f, &findings_by_type, &type_ids)?;
+        write!(f, "<script>\n{SCRIPT}</script>\n")?;
+        writeln!(f, "</body>")?;
+        writeln!(f, "</html>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, Finding, HtmlReport};
+    use crate::error::ErrorLevel;
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            "Option&lt;&amp;&#39;a str&gt; &quot;x&quot;",
+            escape("Option<&'a str> \"x\"")
+        );
+    }
+
+    #[test]
+    fn search_skips_snippets() {
+        let report = HtmlReport {
+            title: "Report".into(),
+            findings: vec![Finding {
+                crate_name: "external_lib".into(),
+                type_name: "external_lib::SomeStruct".into(),
+                level: ErrorLevel::Error,
+                location: "struct field".into(),
+                item: "test_crate::Foo::field".into(),
+                used_in: Some("src/lib.rs:3:16".into()),
+                rendered: "error[CET0001]: pub field: OtherType".into(),
+            }],
+            summary: None,
+        };
+        assert!(report.to_string().contains(
+            "data-search=\"external_lib external_lib::somestruct struct field \
+            test_crate::foo::field\""
+        ));
+    }
+}
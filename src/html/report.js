@@ -0,0 +1,29 @@
+// Hides the findings that don't match the search text, level, and crate filters
+function applyFilters() {
+    const query = document.getElementById("search").value.toLowerCase();
+    const level = document.getElementById("level").value;
+    const crate = document.getElementById("crate").value;
+    let shown = 0;
+    for (const row of document.querySelectorAll("#findings tbody tr")) {
+        const visible = (level === "" || row.dataset.level === level)
+            && (crate === "" || row.dataset.crate === crate)
+            && row.dataset.search.includes(query);
+        row.hidden = !visible;
+        if (visible) {
+            shown += 1;
+        }
+    }
+    document.getElementById("shown").textContent = shown;
+}
+
+for (const id of ["search", "level", "crate"]) {
+    document.getElementById(id).addEventListener("input", applyFilters);
+}
+
+// Links in the crate summary show only the findings for that crate
+for (const link of document.querySelectorAll("[data-filter-crate]")) {
+    link.addEventListener("click", () => {
+        document.getElementById("crate").value = link.dataset.filterCrate;
+        applyFilters();
+    });
+}
@@ -8,8 +8,14 @@ pub(crate) const NEW_ISSUE_URL: &str =
 
 pub mod cargo;
 pub mod config;
+pub mod diagnostic;
+pub mod doctor;
 pub mod error;
+pub mod fingerprint;
+pub mod html;
 pub mod path;
+pub mod report;
+pub mod rustdoc_json;
 pub mod visitor;
 
 /// A macro for attaching info to error messages pointing to the line of code responsible for the error.
@@ -5,25 +5,45 @@
 
 use anyhow::{anyhow, bail};
 use anyhow::{Context, Result};
-use cargo_check_external_types::cargo::CargoRustDocJson;
-use cargo_check_external_types::error::{ErrorPrinter, ValidationError};
+use cargo_check_external_types::cargo::{
+    dependency_versions, lib_target, local_sources, CargoOptions, CargoRustDocJson, CargoTarget,
+    Toolchain,
+};
+use cargo_check_external_types::config::Config;
+use cargo_check_external_types::diagnostic::{ColorChoice, OutputStream};
+use cargo_check_external_types::doctor::Doctor;
+use cargo_check_external_types::error::{ErrorCode, ErrorPrinter, ValidationErrors};
+use cargo_check_external_types::fingerprint::stable_hash;
 use cargo_check_external_types::here;
+use cargo_check_external_types::html::HtmlReport;
+use cargo_check_external_types::report::{DependencyReport, MarkdownTable, Summary};
 use cargo_check_external_types::visitor::Visitor;
-use cargo_metadata::{CargoOpt, Metadata};
+use cargo_metadata::{CargoOpt, Metadata, Package};
 use clap::Parser;
-use std::borrow::Cow;
 use std::fmt;
 use std::fs;
+use std::iter;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
 use std::process;
 use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
 use tracing_subscriber::prelude::*;
 use tracing_subscriber::EnvFilter;
+use wildmatch::WildMatch;
+
+/// Default for `--max-powerset-features`, which limits `--feature-powerset` to 256 runs
+const DEFAULT_MAX_POWERSET_FEATURES: usize = 8;
 
 #[derive(Debug, Eq, PartialEq)]
 enum OutputFormat {
     Errors,
     MarkdownTable,
+    DependencyReport,
+    Summary,
+    Html,
 }
 
 impl fmt::Display for OutputFormat {
@@ -31,6 +51,9 @@ impl fmt::Display for OutputFormat {
         f.write_str(match self {
             Self::Errors => "errors",
             Self::MarkdownTable => "markdown-table",
+            Self::DependencyReport => "dependency-report",
+            Self::Summary => "summary",
+            Self::Html => "html",
         })
     }
 }
@@ -42,8 +65,11 @@ impl FromStr for OutputFormat {
         match s {
             "errors" => Ok(OutputFormat::Errors),
             "markdown-table" => Ok(OutputFormat::MarkdownTable),
+            "dependency-report" => Ok(OutputFormat::DependencyReport),
+            "summary" => Ok(OutputFormat::Summary),
+            "html" => Ok(OutputFormat::Html),
             _ => Err(anyhow!(
-                "invalid output format: {}. Expected `errors` or `markdown-table`.",
+                "invalid output format: {}. Expected `errors`, `markdown-table`, `dependency-report`, `summary`, or `html`.",
                 s
             )),
         }
@@ -53,27 +79,148 @@ impl FromStr for OutputFormat {
 #[derive(clap::Args, Debug, Eq, PartialEq)]
 struct CheckExternalTypesArgs {
     /// Enables all crate features
-    #[clap(long, conflicts_with = "no-default-features")]
+    #[clap(long, conflicts_with = "no-default-features", global = true)]
     all_features: bool,
     /// Disables default features
-    #[clap(long, conflicts_with = "all-features")]
+    #[clap(long, conflicts_with = "all-features", global = true)]
     no_default_features: bool,
     /// Comma delimited list of features to enable in the crate
-    #[clap(long, use_value_delimiter = true)]
+    #[clap(long, use_value_delimiter = true, global = true)]
     features: Option<Vec<String>>,
+    /// Checks the crate with no features, and then with each feature enabled on its own
+    #[clap(long, conflicts_with_all = &["all-features", "no-default-features", "features", "feature-powerset"])]
+    each_feature: bool,
+    /// Checks the crate with every combination of features (other than `default` and the
+    /// implicit features of optional dependencies)
+    #[clap(long, conflicts_with_all = &["all-features", "no-default-features", "features", "each-feature"])]
+    feature_powerset: bool,
+    /// Most features that `--feature-powerset` will check every combination of, since each
+    /// additional feature doubles the number of times the crate is checked
+    #[clap(long, value_name = "N", default_value_t = DEFAULT_MAX_POWERSET_FEATURES)]
+    max_powerset_features: usize,
+    /// Target triple to check the crate for. Can be given more than once to check several targets
+    #[clap(long = "target", value_name = "TRIPLE", global = true)]
+    targets: Vec<String>,
+    /// Name of a binary target to check instead of the library
+    #[clap(long, value_name = "NAME", conflicts_with = "example", global = true)]
+    bin: Option<String>,
+    /// Name of an example target to check instead of the library
+    #[clap(long, value_name = "NAME", conflicts_with = "bin", global = true)]
+    example: Option<String>,
     /// Path to the Cargo manifest
-    #[clap(long)]
+    #[clap(long, global = true)]
     manifest_path: Option<PathBuf>,
 
     /// Path to config toml to read
-    #[clap(long)]
+    #[clap(long, global = true)]
     config: Option<PathBuf>,
     /// Enable verbose output for debugging
-    #[clap(short, long)]
+    #[clap(short, long, global = true)]
     verbose: bool,
     /// Format to output results in
     #[clap(long, default_value_t = OutputFormat::Errors)]
     output_format: OutputFormat,
+    /// Reports each unapproved external type once, along with every place that references it
+    /// (only used by the `errors` output format)
+    #[clap(long)]
+    group_by_type: bool,
+    /// Splits the table into one table per crate (only used by the `markdown-table` output
+    /// format)
+    #[clap(long)]
+    group_by_crate: bool,
+    /// When to color the output: `auto`, `always`, or `never` (`auto` colors the output when
+    /// it's written to a terminal, unless `NO_COLOR` is set)
+    #[clap(long, value_name = "WHEN", default_value_t = ColorChoice::Auto, global = true)]
+    color: ColorChoice,
+    /// Stream to print errors and warnings to: `stdout` or `stderr` (only used by the `errors`
+    /// output format)
+    #[clap(long, value_name = "STREAM", default_value_t = OutputStream::Stdout)]
+    message_stream: OutputStream,
+    /// Prints a summary of the unapproved external type references after the output (or within
+    /// the page, for the `html` output format), with counts per external crate, kind of location,
+    /// top-level module, and type
+    #[clap(long)]
+    summary: bool,
+    /// Number of the most referenced external types to list in the summary
+    #[clap(long, value_name = "N", default_value_t = 10)]
+    summary_top_types: usize,
+    /// Sets `RUSTC_BOOTSTRAP=1` for cargo so that rustdoc's JSON output can be produced
+    /// with a stable toolchain
+    #[clap(long, global = true)]
+    allow_bootstrap: bool,
+    /// Rustup toolchain to run rustdoc with, or `auto` to use the first installed toolchain
+    /// that produces a supported rustdoc JSON format version
+    #[clap(long, value_name = "NAME", global = true)]
+    toolchain: Option<Toolchain>,
+    /// Always run rustdoc, even if the JSON output from a previous run is still up to date
+    #[clap(long, global = true)]
+    no_cache: bool,
+    /// Number of feature sets and targets to check at the same time (defaults to 1)
+    #[clap(short, long, value_name = "N", global = true)]
+    jobs: Option<NonZeroUsize>,
+    #[clap(flatten)]
+    cargo: CargoArgs,
+    #[clap(subcommand)]
+    command: Option<Command>,
+    /// Prints a detailed explanation of an error code (such as `CET0001`) instead of checking
+    /// the crate
+    #[clap(long, value_name = "CODE")]
+    explain: Option<ErrorCode>,
+    /// Extra arguments to pass to rustdoc (for example, `-- --cfg docsrs`)
+    #[clap(last = true, value_name = "RUSTDOC_ARGS")]
+    rustdoc_args: Vec<String>,
+}
+
+#[derive(clap::Subcommand, Debug, Eq, PartialEq)]
+enum Command {
+    /// Diagnoses the environment: the toolchain, its rustdoc JSON support and format version,
+    /// where the JSON output is read from, the enabled features, and the config file
+    Doctor,
+}
+
+/// Options that are passed through to cargo
+#[derive(clap::Args, Debug, Default, Eq, PartialEq)]
+struct CargoArgs {
+    /// Require Cargo.lock to be up to date
+    #[clap(long, global = true)]
+    locked: bool,
+    /// Require Cargo.lock and cache to be up to date
+    #[clap(long, global = true)]
+    frozen: bool,
+    /// Run without accessing the network
+    #[clap(long, global = true)]
+    offline: bool,
+    /// Directory for all generated artifacts (defaults to the workspace's target directory)
+    #[clap(long, value_name = "DIRECTORY", global = true)]
+    target_dir: Option<PathBuf>,
+    /// Override a cargo configuration value (this is cargo's `--config` option)
+    #[clap(long, value_name = "KEY=VALUE", global = true)]
+    cargo_config: Vec<String>,
+    /// Unstable (nightly-only) flags to pass to cargo
+    #[clap(short = 'Z', value_name = "FLAG", global = true)]
+    unstable_flags: Vec<String>,
+}
+
+impl CheckExternalTypesArgs {
+    fn cargo_target(&self) -> CargoTarget {
+        match (&self.bin, &self.example) {
+            (Some(bin), _) => CargoTarget::Bin(bin.clone()),
+            (_, Some(example)) => CargoTarget::Example(example.clone()),
+            _ => CargoTarget::Lib,
+        }
+    }
+}
+
+impl CargoArgs {
+    fn options(&self) -> CargoOptions {
+        CargoOptions {
+            locked: self.locked,
+            frozen: self.frozen,
+            offline: self.offline,
+            config: self.cargo_config.clone(),
+            unstable_flags: self.unstable_flags.clone(),
+        }
+    }
 }
 
 #[derive(Parser, Debug, Eq, PartialEq)]
@@ -122,25 +269,187 @@ fn run_main() -> Result<(), Error> {
             .init();
     }
 
-    let config = if let Some(config_path) = &args.config {
+    args.color.set_override();
+
+    if let Some(code) = args.explain {
+        println!("{code}: {}", code.explanation());
+        return Ok(());
+    }
+
+    if args.command == Some(Command::Doctor) {
+        return run_doctor(&args);
+    }
+
+    let mut config: Config = if let Some(config_path) = &args.config {
         let contents = fs::read_to_string(config_path).context("failed to read config file")?;
         toml::from_str(&contents).context("failed to parse config file")?
     } else {
         Default::default()
     };
+    if args.output_format == OutputFormat::DependencyReport {
+        // Allowed types are still exposed in the public API, so they must be counted
+        // in order to tell which dependencies could be made private.
+        config.allowed_external_types.clear();
+    }
+
+    let cargo_options = args.cargo.options();
+    let (cargo_metadata, crate_path, target_dir) = load_cargo_metadata(&args)?;
+    let cargo_features = resolve_features(&cargo_metadata)?;
+    let root_package = cargo_metadata.root_package().context(here!())?;
+    let crate_versions = dependency_versions(&cargo_metadata, root_package)?;
+    let cache_sources = if args.no_cache {
+        None
+    } else {
+        Some(local_sources(&cargo_metadata, root_package)?)
+    };
+    let cargo_target = args.cargo_target();
+    let crate_name = cargo_target.find(root_package)?.name.replace('-', "_");
+    if cargo_target != CargoTarget::Lib {
+        if let Some(lib) = lib_target(root_package) {
+            // Types from the package's own library aren't external to its binaries and examples
+            let lib_types = format!("{}::*", lib.name.replace('-', "_"));
+            config
+                .allowed_external_types
+                .push(WildMatch::new(&lib_types));
+        }
+    }
+    let feature_sets = if args.each_feature {
+        each_feature(root_package)
+    } else if args.feature_powerset {
+        feature_powerset(root_package, args.max_powerset_features)?
+    } else {
+        vec![cargo_features]
+    };
+    let toolchain = args
+        .toolchain
+        .unwrap_or_default()
+        .resolve(
+            &crate_path,
+            &target_dir.join("check-external-types").join("probe"),
+            args.allow_bootstrap,
+        )
+        .context(here!())?;
+    let targets: Vec<Option<String>> = if args.targets.is_empty() {
+        vec![None]
+    } else {
+        args.targets.iter().cloned().map(Some).collect()
+    };
+
+    let many_feature_sets = feature_sets.len() > 1;
+    let many_targets = targets.len() > 1;
+    let runs: Vec<(String, &Vec<String>, &Option<String>)> = feature_sets
+        .iter()
+        .flat_map(|features| {
+            targets.iter().map(move |target| {
+                let run_name = run_name(
+                    many_feature_sets.then_some(features),
+                    target.as_deref().filter(|_| many_targets),
+                );
+                (run_name, features, target)
+            })
+        })
+        .collect();
+    // Cargo locks its build directory while it runs, so each thread builds in a directory of its
+    // own to let rustdoc runs overlap. This costs disk space and building dependencies once per
+    // thread, so the first thread keeps the usual directory and the others reuse theirs for
+    // every run they pick up.
+    let jobs = args.jobs.map(NonZeroUsize::get).unwrap_or(1);
+    let errors = parallel_map(&runs, jobs, |worker, (run_name, features, target)| {
+        if run_name.is_empty() {
+            eprintln!("Running rustdoc to produce json doc output...");
+        } else {
+            eprintln!("Running rustdoc to produce json doc output with {run_name}...");
+        }
+        let package =
+            CargoRustDocJson::new(&crate_name, &crate_path, &target_dir, features.to_vec())
+                .with_target((*target).clone())
+                .with_cargo_target(cargo_target.clone())
+                .with_cargo_options(cargo_options.clone())
+                .with_rustdoc_args(args.rustdoc_args.clone())
+                .with_allow_bootstrap(args.allow_bootstrap)
+                .with_toolchain(toolchain.clone())
+                .with_cache_sources(cache_sources.clone())
+                .with_output_name(many_feature_sets.then(|| feature_set_file_name(features)))
+                .with_build_dir((worker > 0).then(|| format!("job-{worker}")))
+                .run()
+                .context(here!())?;
 
+        if run_name.is_empty() {
+            eprintln!("Examining all public types...");
+        } else {
+            eprintln!("Examining all public types with {run_name}...");
+        }
+        Visitor::new(config.clone(), package)?
+            .with_crate_versions(crate_versions.clone())
+            .visit_all()
+    })?;
+    let runs = runs
+        .into_iter()
+        .zip(errors)
+        .map(|((run_name, ..), errors)| (run_name, errors));
+    let errors = ValidationErrors::merge(runs);
+    match args.output_format {
+        OutputFormat::Errors => {
+            ErrorPrinter::new(&cargo_metadata.workspace_root)
+                .with_group_by_type(args.group_by_type)
+                .with_color(args.color)
+                .with_stream(args.message_stream)
+                .with_config(config.clone())
+                .pretty_print_errors(&errors);
+        }
+        OutputFormat::MarkdownTable => {
+            let table = MarkdownTable::new(&errors).with_group_by_crate(args.group_by_crate);
+            print!("{}", table);
+        }
+        OutputFormat::DependencyReport => {
+            let report = DependencyReport::new(&cargo_metadata, root_package, &errors)?;
+            print!("{}", report);
+        }
+        OutputFormat::Summary => {
+            print!("{}", Summary::new(&errors, args.summary_top_types));
+        }
+        OutputFormat::Html => {
+            let mut printer =
+                ErrorPrinter::new(&cargo_metadata.workspace_root).with_config(config.clone());
+            let title = format!("External types in {crate_name}");
+            let mut report = HtmlReport::new(title, &errors, &mut printer);
+            if args.summary {
+                report = report.with_summary(Summary::new(&errors, args.summary_top_types));
+            }
+            print!("{}", report);
+        }
+    }
+    // The HTML report includes the summary in the page instead
+    if args.summary
+        && args.output_format != OutputFormat::Summary
+        && args.output_format != OutputFormat::Html
+    {
+        println!();
+        print!("{}", Summary::new(&errors, args.summary_top_types));
+    }
+    if args.output_format == OutputFormat::Errors && errors.error_count() > 0 {
+        return Err(Error::ValidationErrors);
+    }
+
+    Ok(())
+}
+
+/// Runs `cargo metadata` for the crate being checked, and returns the metadata along with
+/// the crate's path and the target directory to document it in.
+fn load_cargo_metadata(args: &CheckExternalTypesArgs) -> Result<(Metadata, PathBuf, PathBuf)> {
     let mut cargo_metadata_cmd = cargo_metadata::MetadataCommand::new();
+    cargo_metadata_cmd.other_options(args.cargo.options().args());
     if args.all_features {
         cargo_metadata_cmd.features(CargoOpt::AllFeatures);
     }
     if args.no_default_features {
         cargo_metadata_cmd.features(CargoOpt::NoDefaultFeatures);
     }
-    if let Some(features) = args.features {
-        cargo_metadata_cmd.features(CargoOpt::SomeFeatures(features));
+    if let Some(features) = &args.features {
+        cargo_metadata_cmd.features(CargoOpt::SomeFeatures(features.clone()));
     }
-    let crate_path = if let Some(manifest_path) = args.manifest_path {
-        cargo_metadata_cmd.manifest_path(&manifest_path);
+    let crate_path = if let Some(manifest_path) = &args.manifest_path {
+        cargo_metadata_cmd.manifest_path(manifest_path);
         manifest_path
             .canonicalize()
             .context(here!())?
@@ -154,56 +463,54 @@ fn run_main() -> Result<(), Error> {
             .context(here!())?
     };
     let cargo_metadata = cargo_metadata_cmd.exec().context(here!())?;
-    let cargo_features = resolve_features(&cargo_metadata)?;
+    let target_dir = match &args.cargo.target_dir {
+        // Cargo is run from the crate path, so a relative target directory must be made absolute
+        Some(target_dir) => std::env::current_dir().context(here!())?.join(target_dir),
+        None => cargo_metadata.target_directory.clone().into_std_path_buf(),
+    };
+    Ok((cargo_metadata, crate_path, target_dir))
+}
 
-    eprintln!("Running rustdoc to produce json doc output...");
-    let package = CargoRustDocJson::new(
-        &*cargo_metadata
-            .root_package()
-            .as_ref()
-            .map(|package| Cow::Borrowed(package.name.as_str()))
-            .unwrap_or_else(|| crate_path.file_name().expect("file name").to_string_lossy()),
-        &crate_path,
-        &cargo_metadata.target_directory,
-        cargo_features,
-    )
-    .run()
-    .context(here!())?;
-
-    eprintln!("Examining all public types...");
-    let errors = Visitor::new(config, package)?.visit_all()?;
-    match args.output_format {
-        OutputFormat::Errors => {
-            ErrorPrinter::new(&cargo_metadata.workspace_root).pretty_print_errors(&errors);
-            if errors.error_count() > 0 {
-                return Err(Error::ValidationErrors);
-            }
-        }
-        OutputFormat::MarkdownTable => {
-            println!("| Crate | Type | Used In |");
-            println!("| ---   | ---  | ---     |");
-            let mut rows = Vec::new();
-            for error in errors.iter() {
-                if let ValidationError::UnapprovedExternalTypeRef { .. } = error {
-                    let type_name = error.type_name();
-                    let crate_name = &type_name[0..type_name.find("::").unwrap_or(type_name.len())];
-                    let location = error.location().unwrap();
-                    rows.push(format!(
-                        "| {} | {} | {}:{}:{} |",
-                        crate_name,
-                        type_name,
-                        location.filename.to_string_lossy(),
-                        location.begin.0,
-                        location.begin.1
-                    ));
-                }
-            }
-            rows.sort();
-            rows.into_iter().for_each(|row| println!("{}", row));
-        }
-    }
+/// Diagnoses the environment instead of checking the crate.
+fn run_doctor(args: &CheckExternalTypesArgs) -> Result<(), Error> {
+    let (cargo_metadata, crate_path, target_dir) = load_cargo_metadata(args)?;
+    let features = resolve_features(&cargo_metadata)?;
+    let root_package = cargo_metadata.root_package().context(here!())?;
+    let crate_name = args
+        .cargo_target()
+        .find(root_package)?
+        .name
+        .replace('-', "_");
+    let targets: Vec<Option<String>> = if args.targets.is_empty() {
+        vec![None]
+    } else {
+        args.targets.iter().cloned().map(Some).collect()
+    };
+    let json_paths = targets
+        .into_iter()
+        .map(|target| {
+            CargoRustDocJson::new(&crate_name, &crate_path, &target_dir, features.clone())
+                .with_target(target)
+                .output_path()
+        })
+        .collect();
 
-    Ok(())
+    let diagnosis = Doctor {
+        toolchain: args.toolchain.clone().unwrap_or_default(),
+        allow_bootstrap: args.allow_bootstrap,
+        probe_path: target_dir.join("check-external-types").join("probe"),
+        crate_path,
+        json_paths,
+        features,
+        config_path: args.config.clone(),
+    }
+    .diagnose();
+    print!("{diagnosis}");
+    if diagnosis.has_errors() {
+        Err(Error::ValidationErrors)
+    } else {
+        Ok(())
+    }
 }
 
 fn resolve_features(metadata: &Metadata) -> Result<Vec<String>> {
@@ -229,6 +536,142 @@ fn resolve_features(metadata: &Metadata) -> Result<Vec<String>> {
     }
 }
 
+/// Returns the feature sets to check for `--each-feature`: no features, and then each feature on its own.
+fn each_feature(package: &Package) -> Vec<Vec<String>> {
+    let mut features: Vec<&String> = package.features.keys().collect();
+    features.sort();
+    iter::once(Vec::new())
+        .chain(features.into_iter().map(|feature| vec![feature.clone()]))
+        .collect()
+}
+
+/// Returns every combination of the package's features, other than `default` (which is a
+/// combination of the others) and the implicit features of optional dependencies.
+///
+/// Fails if there are more than `max_features` features, since the crate is checked once for
+/// each combination.
+fn feature_powerset(package: &Package, max_features: usize) -> Result<Vec<Vec<String>>> {
+    let mut features: Vec<&String> = package
+        .features
+        .iter()
+        .filter(|&(name, enables)| {
+            let implicit = enables.len() == 1 && enables[0] == format!("dep:{name}");
+            name != "default" && !implicit
+        })
+        .map(|(name, _)| name)
+        .collect();
+    features.sort();
+    if features.len() > max_features {
+        let count = features.len();
+        let runs = u32::try_from(count)
+            .ok()
+            .and_then(|count| 1usize.checked_shl(count))
+            .map(|runs| runs.to_string())
+            .unwrap_or_else(|| format!("2^{count}"));
+        bail!(
+            "`--feature-powerset` would check the crate {runs} times for its {count} features, \
+            which is more than the limit of {max_features} features. Raise the limit with \
+            `--max-powerset-features {count}`, or use `--each-feature` instead."
+        );
+    }
+    let mut sets = vec![Vec::new()];
+    for feature in features {
+        let with_feature: Vec<Vec<String>> = sets
+            .iter()
+            .map(|set| {
+                let mut set = set.clone();
+                set.push(feature.clone());
+                set
+            })
+            .collect();
+        sets.extend(with_feature);
+    }
+    sets.sort_by(|a, b| (a.len(), a).cmp(&(b.len(), b)));
+    Ok(sets)
+}
+
+/// Returns a human readable name for a feature set.
+fn feature_set_name(features: &[String]) -> String {
+    if features.is_empty() {
+        "no features".into()
+    } else {
+        format!("features `{}`", features.join(","))
+    }
+}
+
+/// Returns a name for a feature set that can be used in file names. Feature sets with names
+/// that would need to be changed to be used in a file name are identified by a hash instead.
+fn feature_set_file_name(features: &[String]) -> String {
+    if features.is_empty() {
+        return "no-features".into();
+    }
+    let name = features.join("+");
+    let is_file_name = name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+'));
+    if is_file_name && name.len() <= 64 {
+        name
+    } else {
+        // Feature names can't contain commas
+        let hash = stable_hash(features.join(",").as_bytes());
+        format!("features-{hash:016x}")
+    }
+}
+
+/// Calls `f` on each input with up to `jobs` threads, and returns the results in the same order
+/// as the inputs. `f` is also given the index of the thread calling it (from 0 to `jobs - 1`).
+/// Once a call fails, no more calls are started, and the error from the first input that failed
+/// is returned.
+fn parallel_map<T: Sync, R: Send>(
+    inputs: &[T],
+    jobs: usize,
+    f: impl Fn(usize, &T) -> Result<R> + Sync,
+) -> Result<Vec<R>> {
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results: Mutex<Vec<Option<Result<R>>>> = Mutex::new(inputs.iter().map(|_| None).collect());
+    let work = |worker| {
+        while !failed.load(Ordering::SeqCst) {
+            let index = next.fetch_add(1, Ordering::SeqCst);
+            let Some(input) = inputs.get(index) else {
+                break;
+            };
+            let result = f(worker, input);
+            if result.is_err() {
+                failed.store(true, Ordering::SeqCst);
+            }
+            results.lock().expect("no panics while locked")[index] = Some(result);
+        }
+    };
+    thread::scope(|scope| {
+        for worker in 1..jobs.min(inputs.len()) {
+            scope.spawn(move || work(worker));
+        }
+        work(0);
+    });
+    // Inputs after a failure might not have been processed, so stop at the first failure
+    results
+        .into_inner()
+        .expect("no panics while locked")
+        .into_iter()
+        .map_while(|result| result)
+        .collect()
+}
+
+/// Returns a human readable name for one configuration the crate is checked in, which is used
+/// to tag errors that were only found in some configurations. The feature set and target are
+/// only included in the name when more than one of them is being checked.
+fn run_name(features: Option<&Vec<String>>, target: Option<&str>) -> String {
+    let mut parts = Vec::new();
+    if let Some(features) = features {
+        parts.push(feature_set_name(features));
+    }
+    if let Some(target) = target {
+        parts.push(format!("target `{target}`"));
+    }
+    parts.join(" on ")
+}
+
 #[cfg(test)]
 mod arg_parse_tests {
     use super::*;
@@ -241,10 +684,30 @@ mod arg_parse_tests {
                 all_features: false,
                 no_default_features: false,
                 features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
                 manifest_path: None,
                 config: None,
                 verbose: false,
                 output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
             }),
             Args::try_parse_from(["cargo", "check-external-types"]).unwrap()
         );
@@ -257,10 +720,30 @@ mod arg_parse_tests {
                 all_features: true,
                 no_default_features: false,
                 features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
                 manifest_path: None,
                 config: None,
                 verbose: false,
                 output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
             }),
             Args::try_parse_from(["cargo", "check-external-types", "--all-features"]).unwrap()
         );
@@ -273,10 +756,30 @@ mod arg_parse_tests {
                 all_features: false,
                 no_default_features: true,
                 features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
                 manifest_path: None,
                 config: None,
                 verbose: false,
                 output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
             }),
             Args::try_parse_from(["cargo", "check-external-types", "--no-default-features"])
                 .unwrap()
@@ -290,10 +793,30 @@ mod arg_parse_tests {
                 all_features: false,
                 no_default_features: false,
                 features: Some(vec!["foo".into(), "bar".into()]),
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
                 manifest_path: None,
                 config: None,
                 verbose: false,
                 output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
             }),
             Args::try_parse_from(["cargo", "check-external-types", "--features", "foo,bar"])
                 .unwrap()
@@ -307,10 +830,30 @@ mod arg_parse_tests {
                 all_features: false,
                 no_default_features: false,
                 features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
                 manifest_path: Some("test-path".into()),
                 config: None,
                 verbose: false,
                 output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
             }),
             Args::try_parse_from([
                 "cargo",
@@ -329,10 +872,30 @@ mod arg_parse_tests {
                 all_features: false,
                 no_default_features: false,
                 features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
                 manifest_path: None,
                 config: None,
                 verbose: true,
                 output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
             }),
             Args::try_parse_from(["cargo", "check-external-types", "--verbose"]).unwrap()
         );
@@ -345,10 +908,30 @@ mod arg_parse_tests {
                 all_features: false,
                 no_default_features: false,
                 features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
                 manifest_path: None,
                 config: None,
                 verbose: false,
                 output_format: OutputFormat::MarkdownTable,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
             }),
             Args::try_parse_from([
                 "cargo",
@@ -360,6 +943,522 @@ mod arg_parse_tests {
         );
     }
 
+    #[test]
+    fn output_format_dependency_report() {
+        assert_eq!(
+            Args::CheckExternalTypes(CheckExternalTypesArgs {
+                all_features: false,
+                no_default_features: false,
+                features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
+                manifest_path: None,
+                config: None,
+                verbose: false,
+                output_format: OutputFormat::DependencyReport,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
+            }),
+            Args::try_parse_from([
+                "cargo",
+                "check-external-types",
+                "--output-format",
+                "dependency-report"
+            ])
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn each_feature() {
+        assert_eq!(
+            Args::CheckExternalTypes(CheckExternalTypesArgs {
+                all_features: false,
+                no_default_features: false,
+                features: None,
+                each_feature: true,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
+                manifest_path: None,
+                config: None,
+                verbose: false,
+                output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
+            }),
+            Args::try_parse_from(["cargo", "check-external-types", "--each-feature"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn feature_powerset() {
+        assert_eq!(
+            Args::CheckExternalTypes(CheckExternalTypesArgs {
+                all_features: false,
+                no_default_features: false,
+                features: None,
+                each_feature: false,
+                feature_powerset: true,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
+                manifest_path: None,
+                config: None,
+                verbose: false,
+                output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
+            }),
+            Args::try_parse_from(["cargo", "check-external-types", "--feature-powerset"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn targets() {
+        assert_eq!(
+            Args::CheckExternalTypes(CheckExternalTypesArgs {
+                all_features: false,
+                no_default_features: false,
+                features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![
+                    "wasm32-unknown-unknown".into(),
+                    "x86_64-pc-windows-msvc".into()
+                ],
+                bin: None,
+                example: None,
+                manifest_path: None,
+                config: None,
+                verbose: false,
+                output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
+            }),
+            Args::try_parse_from([
+                "cargo",
+                "check-external-types",
+                "--target",
+                "wasm32-unknown-unknown",
+                "--target",
+                "x86_64-pc-windows-msvc"
+            ])
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn bin() {
+        assert_eq!(
+            Args::CheckExternalTypes(CheckExternalTypesArgs {
+                all_features: false,
+                no_default_features: false,
+                features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: Some("foo".into()),
+                example: None,
+                manifest_path: None,
+                config: None,
+                verbose: false,
+                output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
+            }),
+            Args::try_parse_from(["cargo", "check-external-types", "--bin", "foo"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn example() {
+        assert_eq!(
+            Args::CheckExternalTypes(CheckExternalTypesArgs {
+                all_features: false,
+                no_default_features: false,
+                features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: Some("foo".into()),
+                manifest_path: None,
+                config: None,
+                verbose: false,
+                output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
+            }),
+            Args::try_parse_from(["cargo", "check-external-types", "--example", "foo"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn allow_bootstrap() {
+        assert_eq!(
+            Args::CheckExternalTypes(CheckExternalTypesArgs {
+                all_features: false,
+                no_default_features: false,
+                features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
+                manifest_path: None,
+                config: None,
+                verbose: false,
+                output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: true,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
+            }),
+            Args::try_parse_from(["cargo", "check-external-types", "--allow-bootstrap"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn no_cache() {
+        assert_eq!(
+            Args::CheckExternalTypes(CheckExternalTypesArgs {
+                all_features: false,
+                no_default_features: false,
+                features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
+                manifest_path: None,
+                config: None,
+                verbose: false,
+                output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: true,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
+            }),
+            Args::try_parse_from(["cargo", "check-external-types", "--no-cache"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn toolchain() {
+        assert_eq!(
+            Args::CheckExternalTypes(CheckExternalTypesArgs {
+                all_features: false,
+                no_default_features: false,
+                features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
+                manifest_path: None,
+                config: None,
+                verbose: false,
+                output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: Some(Toolchain::Named("nightly-2023-05-31".into())),
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
+            }),
+            Args::try_parse_from([
+                "cargo",
+                "check-external-types",
+                "--toolchain",
+                "nightly-2023-05-31"
+            ])
+            .unwrap()
+        );
+        assert_eq!(
+            Args::CheckExternalTypes(CheckExternalTypesArgs {
+                all_features: false,
+                no_default_features: false,
+                features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
+                manifest_path: None,
+                config: None,
+                verbose: false,
+                output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: Some(Toolchain::Auto),
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: None,
+                rustdoc_args: vec![],
+            }),
+            Args::try_parse_from(["cargo", "check-external-types", "--toolchain", "auto"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn doctor() {
+        assert_eq!(
+            Args::CheckExternalTypes(CheckExternalTypesArgs {
+                all_features: false,
+                no_default_features: false,
+                features: Some(vec!["a".into()]),
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
+                manifest_path: None,
+                config: Some("external-types.toml".into()),
+                verbose: false,
+                output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: Default::default(),
+                command: Some(Command::Doctor),
+                rustdoc_args: vec![],
+            }),
+            Args::try_parse_from([
+                "cargo",
+                "check-external-types",
+                "--features",
+                "a",
+                "doctor",
+                "--config",
+                "external-types.toml",
+            ])
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn cargo_options() {
+        assert_eq!(
+            Args::CheckExternalTypes(CheckExternalTypesArgs {
+                all_features: false,
+                no_default_features: false,
+                features: None,
+                each_feature: false,
+                feature_powerset: false,
+                max_powerset_features: DEFAULT_MAX_POWERSET_FEATURES,
+                targets: vec![],
+                bin: None,
+                example: None,
+                manifest_path: None,
+                config: Some("external-types.toml".into()),
+                verbose: false,
+                output_format: OutputFormat::Errors,
+                group_by_type: false,
+                group_by_crate: false,
+                color: ColorChoice::Auto,
+                message_stream: OutputStream::Stdout,
+                summary: false,
+                summary_top_types: 10,
+                allow_bootstrap: false,
+                toolchain: None,
+                no_cache: false,
+                jobs: None,
+                explain: None,
+                cargo: CargoArgs {
+                    locked: true,
+                    frozen: true,
+                    offline: true,
+                    target_dir: Some("some-target".into()),
+                    cargo_config: vec!["net.retry=5".into(), "build.jobs=1".into()],
+                    unstable_flags: vec!["unstable-options".into()],
+                },
+                command: None,
+                rustdoc_args: vec!["--cfg".into(), "docsrs".into()],
+            }),
+            Args::try_parse_from([
+                "cargo",
+                "check-external-types",
+                "--config",
+                "external-types.toml",
+                "--locked",
+                "--frozen",
+                "--offline",
+                "--target-dir",
+                "some-target",
+                "--cargo-config",
+                "net.retry=5",
+                "--cargo-config",
+                "build.jobs=1",
+                "-Z",
+                "unstable-options",
+                "--",
+                "--cfg",
+                "docsrs"
+            ])
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn conflict_bin_example() {
+        assert!(Args::try_parse_from([
+            "cargo",
+            "check-external-types",
+            "--bin",
+            "foo",
+            "--example",
+            "bar"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn conflict_feature_matrix() {
+        // Check that the feature matrix modes conflict with each other and with explicit features
+        for args in [
+            ["--each-feature", "--feature-powerset"],
+            ["--each-feature", "--all-features"],
+            ["--feature-powerset", "--no-default-features"],
+            ["--each-feature", "--features=foo"],
+        ] {
+            assert!(Args::try_parse_from(
+                ["cargo", "check-external-types"].into_iter().chain(args)
+            )
+            .is_err());
+        }
+    }
+
     #[test]
     fn conflict_all_features_no_default_features() {
         // Check `--all-features` and `--no-default-features` conflict
@@ -372,3 +1471,271 @@ mod arg_parse_tests {
         .is_err());
     }
 }
+
+#[cfg(test)]
+mod feature_set_tests {
+    use super::*;
+
+    fn package_with_features(features: &[&str]) -> Package {
+        let features: serde_json::Map<String, serde_json::Value> = features
+            .iter()
+            .map(|&feature| (feature.to_string(), serde_json::json!([])))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "name": "test",
+            "version": "0.1.0",
+            "id": "test 0.1.0 (path+file:///test)",
+            "dependencies": [],
+            "targets": [],
+            "features": features,
+            "manifest_path": "/test/Cargo.toml",
+        }))
+        .unwrap()
+    }
+
+    fn sets(sets: &[&[&str]]) -> Vec<Vec<String>> {
+        sets.iter()
+            .map(|set| set.iter().map(|&feature| feature.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn each_feature_sets() {
+        assert_eq!(
+            sets(&[&[], &["a"], &["b"], &["default"]]),
+            each_feature(&package_with_features(&["default", "b", "a"]))
+        );
+    }
+
+    #[test]
+    fn jobs() {
+        let Args::CheckExternalTypes(args) =
+            Args::try_parse_from(["cargo", "check-external-types", "-j", "4"]).unwrap();
+        assert_eq!(NonZeroUsize::new(4), args.jobs);
+        let Args::CheckExternalTypes(args) =
+            Args::try_parse_from(["cargo", "check-external-types", "--jobs", "2"]).unwrap();
+        assert_eq!(NonZeroUsize::new(2), args.jobs);
+        assert!(Args::try_parse_from(["cargo", "check-external-types", "--jobs", "0"]).is_err());
+    }
+
+    #[test]
+    fn group_by_type() {
+        let Args::CheckExternalTypes(args) =
+            Args::try_parse_from(["cargo", "check-external-types", "--group-by-type"]).unwrap();
+        assert!(args.group_by_type);
+    }
+
+    #[test]
+    fn group_by_crate() {
+        let Args::CheckExternalTypes(args) = Args::try_parse_from([
+            "cargo",
+            "check-external-types",
+            "--output-format",
+            "markdown-table",
+            "--group-by-crate",
+        ])
+        .unwrap();
+        assert_eq!(OutputFormat::MarkdownTable, args.output_format);
+        assert!(args.group_by_crate);
+    }
+
+    #[test]
+    fn color_and_message_stream() {
+        let Args::CheckExternalTypes(args) = Args::try_parse_from([
+            "cargo",
+            "check-external-types",
+            "--color",
+            "never",
+            "--message-stream",
+            "stderr",
+        ])
+        .unwrap();
+        assert_eq!(ColorChoice::Never, args.color);
+        assert_eq!(OutputStream::Stderr, args.message_stream);
+        assert!(
+            Args::try_parse_from(["cargo", "check-external-types", "--color", "sometimes"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let Args::CheckExternalTypes(args) = Args::try_parse_from([
+            "cargo",
+            "check-external-types",
+            "--summary",
+            "--summary-top-types",
+            "3",
+        ])
+        .unwrap();
+        assert!(args.summary);
+        assert_eq!(3, args.summary_top_types);
+        let Args::CheckExternalTypes(args) = Args::try_parse_from([
+            "cargo",
+            "check-external-types",
+            "--output-format",
+            "summary",
+        ])
+        .unwrap();
+        assert_eq!(OutputFormat::Summary, args.output_format);
+        assert!(!args.summary);
+    }
+
+    #[test]
+    fn output_format_html() {
+        let Args::CheckExternalTypes(args) =
+            Args::try_parse_from(["cargo", "check-external-types", "--output-format", "html"])
+                .unwrap();
+        assert_eq!(OutputFormat::Html, args.output_format);
+    }
+
+    #[test]
+    fn explain() {
+        let Args::CheckExternalTypes(args) =
+            Args::try_parse_from(["cargo", "check-external-types", "--explain", "cet0002"])
+                .unwrap();
+        assert_eq!(Some(ErrorCode::FieldsStripped), args.explain);
+        assert!(
+            Args::try_parse_from(["cargo", "check-external-types", "--explain", "CET9999"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn feature_powerset_sets() {
+        assert_eq!(
+            sets(&[
+                &[],
+                &["a"],
+                &["b"],
+                &["c"],
+                &["a", "b"],
+                &["a", "c"],
+                &["b", "c"],
+                &["a", "b", "c"]
+            ]),
+            feature_powerset(&package_with_features(&["c", "b", "a"]), 8).unwrap()
+        );
+        assert_eq!(
+            sets(&[&[]]),
+            feature_powerset(&package_with_features(&[]), 8).unwrap()
+        );
+    }
+
+    #[test]
+    fn feature_powerset_skips_default_and_implicit_features() {
+        let mut package = package_with_features(&["a", "default"]);
+        package
+            .features
+            .insert("optional_dep".into(), vec!["dep:optional_dep".into()]);
+        // Features that enable an optional dependency along with other things are kept
+        package
+            .features
+            .insert("b".into(), vec!["dep:optional_dep".into(), "a".into()]);
+        assert_eq!(
+            sets(&[&[], &["a"], &["b"], &["a", "b"]]),
+            feature_powerset(&package, 8).unwrap()
+        );
+    }
+
+    #[test]
+    fn feature_powerset_limit() {
+        let package = package_with_features(&["a", "b", "c"]);
+        let err = feature_powerset(&package, 2).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("would check the crate 8 times for its 3 features"),
+            "{err}"
+        );
+        assert_eq!(8, feature_powerset(&package, 3).unwrap().len());
+        let Args::CheckExternalTypes(args) = Args::try_parse_from([
+            "cargo",
+            "check-external-types",
+            "--feature-powerset",
+            "--max-powerset-features",
+            "12",
+        ])
+        .unwrap();
+        assert_eq!(12, args.max_powerset_features);
+    }
+
+    #[test]
+    fn run_names() {
+        let features = vec!["a".to_string(), "b".to_string()];
+        assert_eq!("", run_name(None, None));
+        assert_eq!("features `a,b`", run_name(Some(&features), None));
+        assert_eq!("no features", run_name(Some(&vec![]), None));
+        assert_eq!("target `wasm32`", run_name(None, Some("wasm32")));
+        assert_eq!(
+            "features `a,b` on target `wasm32`",
+            run_name(Some(&features), Some("wasm32"))
+        );
+    }
+
+    #[test]
+    fn feature_set_file_names() {
+        let features = |names: &[&str]| {
+            names
+                .iter()
+                .map(|name| name.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!("no-features", feature_set_file_name(&[]));
+        assert_eq!(
+            "default+simple",
+            feature_set_file_name(&features(&["default", "simple"]))
+        );
+        let with_slash = feature_set_file_name(&features(&["dep/feature"]));
+        assert!(with_slash.starts_with("features-"), "{with_slash}");
+        assert_ne!(
+            with_slash,
+            feature_set_file_name(&features(&["dep_feature"]))
+        );
+    }
+
+    #[test]
+    fn parallel_map_keeps_order() {
+        let inputs: Vec<u64> = (0..20).collect();
+        let doubled = parallel_map(&inputs, 4, |_, &input| {
+            // Finish out of order
+            thread::sleep(std::time::Duration::from_millis(20 - input));
+            Ok(input * 2)
+        })
+        .unwrap();
+        assert_eq!(
+            inputs.iter().map(|input| input * 2).collect::<Vec<_>>(),
+            doubled
+        );
+    }
+
+    #[test]
+    fn parallel_map_gives_each_thread_its_own_index() {
+        let inputs: Vec<u64> = (0..20).collect();
+        let workers = parallel_map(&inputs, 3, |worker, _| {
+            thread::sleep(std::time::Duration::from_millis(5));
+            Ok((worker, thread::current().id()))
+        })
+        .unwrap();
+        for (worker, thread_id) in &workers {
+            assert!(*worker < 3);
+            assert!(workers
+                .iter()
+                .all(|(other, other_id)| (other == worker) == (other_id == thread_id)));
+        }
+    }
+
+    #[test]
+    fn parallel_map_stops_at_first_failure() {
+        let calls = AtomicUsize::new(0);
+        let result = parallel_map(&[1, 2, 3, 4], 1, |_, &input| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            if input >= 2 {
+                bail!("failed on {input}")
+            } else {
+                Ok(input)
+            }
+        });
+        assert_eq!("failed on 2", result.unwrap_err().to_string());
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+    }
+}
@@ -3,11 +3,11 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
-use rustdoc_types::{Item, Span};
+use rustdoc_types::{Id, Item, Span};
 use std::fmt;
 
 /// Component type for components in a [`Path`].
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 pub enum ComponentType {
     AssocConst,
     AssocType,
@@ -32,12 +32,19 @@ pub enum ComponentType {
 struct Component {
     typ: ComponentType,
     name: String,
+    /// ID of the item that the component names, if there is one
+    id: Option<Id>,
     span: Option<Span>,
 }
 
 impl Component {
-    fn new(typ: ComponentType, name: String, span: Option<Span>) -> Self {
-        Self { typ, name, span }
+    fn new(typ: ComponentType, name: String, id: Option<Id>, span: Option<Span>) -> Self {
+        Self {
+            typ,
+            name,
+            id,
+            span,
+        }
     }
 }
 
@@ -57,17 +64,29 @@ impl Path {
                 ComponentType::Crate,
                 crate_name.into(),
                 None,
+                None,
             )],
         }
     }
 
     pub fn push(&mut self, typ: ComponentType, item: &Item) {
-        self.push_raw(typ, item.name.as_ref().expect("name"), item.span.as_ref());
+        self.push_raw(
+            typ,
+            item.name.as_ref().expect("name"),
+            Some(&item.id),
+            item.span.as_ref(),
+        );
     }
 
-    pub fn push_raw(&mut self, typ: ComponentType, name: &str, span: Option<&Span>) {
+    pub fn push_raw(
+        &mut self,
+        typ: ComponentType,
+        name: &str,
+        id: Option<&Id>,
+        span: Option<&Span>,
+    ) {
         self.stack
-            .push(Component::new(typ, name.into(), span.cloned()));
+            .push(Component::new(typ, name.into(), id.cloned(), span.cloned()));
     }
 
     /// Returns the span (file + beginning and end positions) of the last `Component` in the stack.
@@ -79,6 +98,63 @@ impl Path {
     pub fn last_type(&self) -> Option<ComponentType> {
         self.stack.last().map(|c| c.typ)
     }
+
+    /// Returns a key that identifies what this path leads to, no matter which public path it was
+    /// reached through: the ID of the outermost item that isn't a module, followed by the names
+    /// of the components after it.
+    ///
+    /// Returns `None` if the path only has modules, or leads to something without an ID.
+    pub fn item_key(&self) -> Option<String> {
+        let start = self.stack.iter().position(|component| {
+            !matches!(component.typ, ComponentType::Crate | ComponentType::Module)
+        })?;
+        let id = self.stack[start].id.as_ref()?;
+        let names: Vec<&str> = self.stack[start + 1..]
+            .iter()
+            .map(|component| component.name.as_str())
+            .collect();
+        Some(format!("{}::{}", id.0, names.join("::")))
+    }
+
+    /// Returns the name of the module directly under the crate that this path is in, or `None`
+    /// if the path is at the crate root.
+    pub fn top_level_module(&self) -> Option<&str> {
+        match self.stack.get(1) {
+            Some(component) if component.typ == ComponentType::Module && self.stack.len() > 2 => {
+                Some(&component.name)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the number of components in the path, including the crate.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Returns the components that come after the first `depth` components.
+    pub fn suffix(&self, depth: usize) -> PathSuffix {
+        PathSuffix {
+            stack: self.stack[depth.min(self.stack.len())..].to_vec(),
+        }
+    }
+
+    /// Returns this path with the components of `suffix` appended.
+    pub fn join(&self, suffix: &PathSuffix) -> Path {
+        let mut path = self.clone();
+        path.stack.extend(suffix.stack.iter().cloned());
+        path
+    }
+}
+
+/// Components at the end of a [`Path`], which can be appended to a different path with
+/// [`Path::join`].
+///
+/// The [`Visitor`](crate::visitor::Visitor) uses this to attribute what it finds in an item
+/// to every path that the item is reachable through, without visiting the item again.
+#[derive(Clone, Debug)]
+pub struct PathSuffix {
+    stack: Vec<Component>,
 }
 
 impl fmt::Display for Path {
@@ -0,0 +1,452 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+use crate::cargo::lib_target;
+use crate::diagnostic::fmt_position;
+use crate::error::{ValidationError, ValidationErrors};
+use crate::here;
+use anyhow::{anyhow, Context, Result};
+use cargo_metadata::{DependencyKind, Metadata, Package};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Dependencies with at most this many exposures are considered cheap to wrap.
+const CHEAP_TO_WRAP_THRESHOLD: usize = 5;
+
+/// Recommendation for what can be done about a dependency, based on how often it is exposed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Recommendation {
+    /// The dependency isn't exposed in the public API at all
+    SafeToMarkPrivate,
+    /// The dependency is exposed in a small number of places that could be wrapped
+    CheapToWrap,
+    /// The dependency is exposed too often to easily remove from the public API
+    WidelyExposed,
+}
+
+impl Recommendation {
+    fn from_exposures(exposures: usize) -> Self {
+        match exposures {
+            0 => Self::SafeToMarkPrivate,
+            1..=CHEAP_TO_WRAP_THRESHOLD => Self::CheapToWrap,
+            _ => Self::WidelyExposed,
+        }
+    }
+}
+
+impl fmt::Display for Recommendation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::SafeToMarkPrivate => "safe to mark private",
+            Self::CheapToWrap => "cheap to wrap",
+            Self::WidelyExposed => "widely exposed",
+        })
+    }
+}
+
+/// Number of public API sites that reference a single normal dependency.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DependencyExposure {
+    /// Package name as it appears in Cargo.toml
+    pub package_name: String,
+    /// Resolved package version
+    pub version: String,
+    /// Crate name that rustdoc uses as the first segment of type paths from this dependency
+    pub crate_name: String,
+    /// Number of public API sites that reference a type from this dependency
+    pub exposures: usize,
+}
+
+impl DependencyExposure {
+    pub fn recommendation(&self) -> Recommendation {
+        Recommendation::from_exposures(self.exposures)
+    }
+}
+
+/// Per-dependency summary of how often each normal dependency is exposed in the public API.
+///
+/// This is intended to help plan semver-breaking releases: dependencies with no exposures
+/// can be made private implementation details, and dependencies with only a few exposures
+/// can be wrapped in newtypes with little effort.
+#[derive(Debug)]
+pub struct DependencyReport {
+    dependencies: Vec<DependencyExposure>,
+}
+
+impl DependencyReport {
+    /// Creates a report for the normal dependencies of `root_package` from the given `errors`.
+    ///
+    /// Every [`ValidationError::UnapprovedExternalTypeRef`] counts as one exposure of the
+    /// crate that the referenced type belongs to.
+    pub fn new(
+        metadata: &Metadata,
+        root_package: &Package,
+        errors: &ValidationErrors,
+    ) -> Result<Self> {
+        let resolve = metadata
+            .resolve
+            .as_ref()
+            .ok_or_else(|| anyhow!("Cargo metadata didn't have resolved nodes"))?;
+        let root_node = resolve
+            .nodes
+            .iter()
+            .find(|&n| n.id == root_package.id)
+            .ok_or_else(|| anyhow!("Failed to find node for root package"))?;
+
+        let mut exposures_by_crate: BTreeMap<&str, usize> = BTreeMap::new();
+        for error in errors.iter() {
+            if let ValidationError::UnapprovedExternalTypeRef { .. } = error {
+                *exposures_by_crate.entry(error.crate_name()).or_default() += 1;
+            }
+        }
+
+        let mut dependencies = Vec::new();
+        for dep in &root_node.deps {
+            if !dep
+                .dep_kinds
+                .iter()
+                .any(|info| info.kind == DependencyKind::Normal)
+            {
+                continue;
+            }
+            let package = &metadata[&dep.pkg];
+            let crate_name = lib_target(package)
+                .map(|target| target.name.replace('-', "_"))
+                .with_context(|| here!("dependency `{}` has no library target", package.name))?;
+            dependencies.push(DependencyExposure {
+                package_name: package.name.clone(),
+                version: package.version.to_string(),
+                exposures: exposures_by_crate
+                    .get(crate_name.as_str())
+                    .copied()
+                    .unwrap_or_default(),
+                crate_name,
+            });
+        }
+        dependencies.sort_by(|a, b| {
+            (a.exposures, &a.package_name, &a.version).cmp(&(
+                b.exposures,
+                &b.package_name,
+                &b.version,
+            ))
+        });
+        Ok(Self { dependencies })
+    }
+
+    pub fn dependencies(&self) -> &[DependencyExposure] {
+        &self.dependencies
+    }
+}
+
+impl fmt::Display for DependencyReport {
+    /// Formats the report as a Markdown table
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "| Dependency | Version | Exposures | Recommendation |")?;
+        writeln!(f, "| ---        | ---     | ---       | ---            |")?;
+        for dep in &self.dependencies {
+            writeln!(
+                f,
+                "| {} | {} | {} | {} |",
+                dep.package_name,
+                dep.version,
+                dep.exposures,
+                dep.recommendation()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Markdown table of every error and warning, with the item and the place in it that each was
+/// found in.
+#[derive(Debug)]
+pub struct MarkdownTable {
+    /// Cells of each row, starting with the crate name
+    rows: Vec<[String; 6]>,
+    group_by_crate: bool,
+}
+
+impl MarkdownTable {
+    const HEADINGS: [&'static str; 6] = ["Crate", "Type", "Level", "Location", "Item", "Used In"];
+
+    pub fn new(errors: &ValidationErrors) -> Self {
+        let mut rows: Vec<[String; 6]> = errors
+            .iter()
+            .map(|error| {
+                [
+                    error.crate_name().to_string(),
+                    error.type_name().to_string(),
+                    error.level().to_string(),
+                    error
+                        .what()
+                        .map(|what| what.kind())
+                        .unwrap_or_default()
+                        .to_string(),
+                    error.in_what_type().unwrap_or_default().to_string(),
+                    error
+                        .location()
+                        .map(|location| fmt_position(&location.filename, location.begin))
+                        .unwrap_or_default(),
+                ]
+            })
+            .collect();
+        rows.sort();
+        Self {
+            rows,
+            group_by_crate: false,
+        }
+    }
+
+    /// Sets whether to split the table into one table per crate, each under a heading with the
+    /// crate's name. Defaults to `false`.
+    pub fn with_group_by_crate(mut self, group_by_crate: bool) -> Self {
+        self.group_by_crate = group_by_crate;
+        self
+    }
+
+    fn fmt_table(f: &mut fmt::Formatter<'_>, headings: &[&str], rows: &[&[String]]) -> fmt::Result {
+        writeln!(f, "| {} |", headings.join(" | "))?;
+        let separators: Vec<String> = headings
+            .iter()
+            .map(|heading| format!("{:<width$}", "---", width = heading.len()))
+            .collect();
+        writeln!(f, "| {} |", separators.join(" | "))?;
+        for row in rows {
+            // Pipes would otherwise end the cell early
+            let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            writeln!(f, "| {} |", cells.join(" | "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for MarkdownTable {
+    /// Formats the table as Markdown, with a heading for each crate when grouping by crate
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.group_by_crate {
+            let rows: Vec<&[String]> = self.rows.iter().map(|row| &row[..]).collect();
+            return Self::fmt_table(f, &Self::HEADINGS, &rows);
+        }
+        let mut by_crate: BTreeMap<&str, Vec<&[String]>> = BTreeMap::new();
+        for row in &self.rows {
+            by_crate.entry(&row[0]).or_default().push(&row[1..]);
+        }
+        for (index, (crate_name, rows)) in by_crate.into_iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "## {crate_name}")?;
+            writeln!(f)?;
+            Self::fmt_table(f, &Self::HEADINGS[1..], &rows)?;
+        }
+        Ok(())
+    }
+}
+
+/// Counts of the unapproved external type references in the public API, broken down by
+/// external crate, by kind of location, by top-level module of the root crate, and by type.
+///
+/// This is intended for tracking progress on removing dependencies from the public API over
+/// time.
+#[derive(Debug)]
+pub struct Summary {
+    total: usize,
+    by_crate: Vec<(String, usize)>,
+    by_location: Vec<(String, usize)>,
+    by_module: Vec<(String, usize)>,
+    /// The most referenced types, limited to the number of types the summary was created with
+    top_types: Vec<(String, usize)>,
+}
+
+impl Summary {
+    /// Creates a summary of the [`ValidationError::UnapprovedExternalTypeRef`]s in `errors`,
+    /// listing up to `top_type_count` of the most referenced types.
+    pub fn new(errors: &ValidationErrors, top_type_count: usize) -> Self {
+        let mut total = 0;
+        let mut by_crate: BTreeMap<String, usize> = BTreeMap::new();
+        let mut by_location: BTreeMap<String, usize> = BTreeMap::new();
+        let mut by_module: BTreeMap<String, usize> = BTreeMap::new();
+        let mut by_type: BTreeMap<String, usize> = BTreeMap::new();
+        for error in errors.iter() {
+            if let ValidationError::UnapprovedExternalTypeRef { what, .. } = error {
+                total += 1;
+                *by_crate.entry(error.crate_name().into()).or_default() += 1;
+                *by_location.entry(what.kind().into()).or_default() += 1;
+                let module = error.top_level_module().unwrap_or("(crate root)");
+                *by_module.entry(module.into()).or_default() += 1;
+                *by_type.entry(error.type_name().into()).or_default() += 1;
+            }
+        }
+        let mut top_types = most_referenced(by_type);
+        top_types.truncate(top_type_count);
+        Self {
+            total,
+            by_crate: most_referenced(by_crate),
+            by_location: most_referenced(by_location),
+            by_module: most_referenced(by_module),
+            top_types,
+        }
+    }
+
+    /// Returns the total number of unapproved external type references.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Returns the number of references to types from each external crate, most referenced first.
+    pub fn by_crate(&self) -> &[(String, usize)] {
+        &self.by_crate
+    }
+
+    /// Returns the number of references in each kind of location (such as struct fields), most
+    /// referenced first.
+    pub fn by_location(&self) -> &[(String, usize)] {
+        &self.by_location
+    }
+
+    /// Returns the number of references in each top-level module of the root crate, most
+    /// referenced first.
+    pub fn by_module(&self) -> &[(String, usize)] {
+        &self.by_module
+    }
+
+    /// Returns the most referenced external types, most referenced first.
+    pub fn top_types(&self) -> &[(String, usize)] {
+        &self.top_types
+    }
+}
+
+/// Sorts counts from most to least, with ties in name order.
+fn most_referenced(counts: BTreeMap<String, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    // The sort is stable, so names stay in order within the same count
+    counts.sort_by(|(_, a), (_, b)| b.cmp(a));
+    counts
+}
+
+impl fmt::Display for Summary {
+    /// Formats the summary as Markdown tables
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "## Summary")?;
+        writeln!(f)?;
+        writeln!(f, "Unapproved external type references: {}", self.total)?;
+        let tables = [
+            ("External Crate", &self.by_crate),
+            ("Location", &self.by_location),
+            ("Module", &self.by_module),
+            ("Most Referenced Type", &self.top_types),
+        ];
+        for (heading, counts) in tables {
+            writeln!(f)?;
+            writeln!(f, "| {heading} | References |")?;
+            writeln!(
+                f,
+                "| {:<width$} | ---        |",
+                "---",
+                width = heading.len()
+            )?;
+            for (name, count) in counts {
+                writeln!(f, "| {name} | {count} |")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Recommendation, Summary};
+    use crate::error::{ErrorLocation, ValidationError, ValidationErrors};
+    use crate::path::Path;
+    use rustdoc_types::Span;
+
+    #[test]
+    fn recommendation_from_exposures() {
+        assert_eq!(
+            Recommendation::SafeToMarkPrivate,
+            Recommendation::from_exposures(0)
+        );
+        assert_eq!(
+            Recommendation::CheapToWrap,
+            Recommendation::from_exposures(1)
+        );
+        assert_eq!(
+            Recommendation::CheapToWrap,
+            Recommendation::from_exposures(5)
+        );
+        assert_eq!(
+            Recommendation::WidelyExposed,
+            Recommendation::from_exposures(6)
+        );
+    }
+
+    #[test]
+    fn summary_counts() {
+        let span = Span {
+            filename: "lib.rs".into(),
+            begin: (1, 0),
+            end: (1, 1),
+        };
+        let error =
+            |type_name: &str, what: ErrorLocation, in_what_type: &str, module: Option<&str>| {
+                ValidationError::unapproved_external_type_ref(
+                    type_name,
+                    &what,
+                    &[],
+                    in_what_type,
+                    Some(&span),
+                )
+                .with_top_level_module(module.map(str::to_string))
+            };
+        let mut errors = ValidationErrors::new();
+        errors.add(error(
+            "one::A",
+            ErrorLocation::ReturnValue,
+            "krate::f",
+            None,
+        ));
+        errors.add(error(
+            "one::B",
+            ErrorLocation::ArgumentNamed("b".into()),
+            "krate::f",
+            None,
+        ));
+        errors.add(error(
+            "two::C",
+            ErrorLocation::StructField,
+            "krate::module::S::c",
+            Some("module"),
+        ));
+        errors.add(error(
+            "one::A",
+            ErrorLocation::StructField,
+            "krate::module::S::a",
+            Some("module"),
+        ));
+        errors.add(ValidationError::fields_stripped(&Path::new("krate")));
+
+        let summary = Summary::new(&errors, 2);
+        fn counts(counts: &[(String, usize)]) -> Vec<(&str, usize)> {
+            counts
+                .iter()
+                .map(|(name, count)| (name.as_str(), *count))
+                .collect()
+        }
+        assert_eq!(4, summary.total());
+        assert_eq!(vec![("one", 3), ("two", 1)], counts(summary.by_crate()));
+        assert_eq!(
+            vec![("struct field", 2), ("argument", 1), ("return value", 1)],
+            counts(summary.by_location())
+        );
+        assert_eq!(
+            vec![("(crate root)", 2), ("module", 2)],
+            counts(summary.by_module())
+        );
+        assert_eq!(
+            vec![("one::A", 2), ("one::B", 1)],
+            counts(summary.top_types())
+        );
+    }
+}
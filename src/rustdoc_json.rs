@@ -0,0 +1,1077 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! Loading of rustdoc JSON output in more than one format version.
+//!
+//! The [`Visitor`](crate::visitor::Visitor) works with the `rustdoc-types` model for
+//! [`FORMAT_VERSION`]. Output in other supported format versions is converted into that
+//! model at the [`serde_json::Value`] level before it is deserialized. Fields that were added
+//! in later versions are ignored by deserialization, so only renamed, restructured, and
+//! removed fields need to be handled by a migration.
+//!
+//! Index entries that the visitor never visits (blanket impls and items from other crates) are
+//! dropped while the output is deserialized, along with the references to them, so that they're
+//! never held in memory.
+
+use crate::here;
+use anyhow::{anyhow, bail, Context, Result};
+use rustdoc_types::{Crate, Id, Item, ItemEnum, ItemSummary, FORMAT_VERSION};
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::marker::PhantomData;
+use std::path::Path;
+
+/// Format versions that can be loaded. Only versions that have been checked against real
+/// rustdoc output are listed, since the format can change in subtle ways between versions.
+pub const SUPPORTED_FORMAT_VERSIONS: &[u32] = &[FORMAT_VERSION, v57::FORMAT_VERSION];
+
+/// Returns the `format_version` of the given rustdoc JSON output. The output is streamed
+/// rather than held in memory, since rustdoc writes the format version last.
+pub fn format_version(reader: impl Read) -> Result<u32> {
+    Ok(peek(reader)?.format_version)
+}
+
+/// Reads the rustdoc JSON output at `path` in any of the [`SUPPORTED_FORMAT_VERSIONS`].
+///
+/// The output of large crates can be hundreds of megabytes, so it's never read into memory
+/// as a whole. Instead, the file is streamed once to peek at the format version, once more to
+/// find the crate of each item if the format version needs it, and once to deserialize it one
+/// item at a time.
+pub fn read_crate(path: &Path) -> Result<Crate> {
+    load(|| {
+        let file = File::open(path).context(here!("failed to open {path:?}"))?;
+        Ok(BufReader::new(file))
+    })
+}
+
+/// Parses rustdoc JSON output in any of the [`SUPPORTED_FORMAT_VERSIONS`].
+pub fn parse_crate(json: &str) -> Result<Crate> {
+    load(|| Ok(json.as_bytes()))
+}
+
+fn load<R: Read>(open: impl Fn() -> Result<R>) -> Result<Crate> {
+    let format = match peek(open()?)?.format_version {
+        FORMAT_VERSION => Format::Current,
+        v57::FORMAT_VERSION => {
+            let ids: ItemCrateIds = serde_json::from_reader(open()?)
+                .context("Failed to parse rustdoc output.")
+                .context(here!())?;
+            let mut crate_ids = ids.index.0;
+            crate_ids.extend(ids.paths.0);
+            Format::V57(v57::Migration::new(crate_ids))
+        }
+        unsupported => bail!(
+            "The version of rustdoc being used produces JSON format version {0}, but \
+            this tool supports format versions {1}. This can happen if the locally \
+            installed version of rustdoc doesn't match the rustdoc JSON types from \
+            the `rustdoc-types` crate.\n\n\
+            If this occurs with the latest Rust nightly and the latest version of this \
+            tool, then this is a bug, and the tool needs to be upgraded to the latest \
+            format version.\n\n\
+            Otherwise, you'll need to determine a Rust nightly version that matches \
+            one of this tool's supported format versions (or vice versa). Running with \
+            `--toolchain auto` will search the installed toolchains for one that matches.",
+            unsupported,
+            supported_format_versions(),
+        ),
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(open()?);
+    CrateSeed(&format)
+        .deserialize(&mut deserializer)
+        .and_then(|package| deserializer.end().map(|_| package))
+        .context("Failed to parse rustdoc output.")
+        .context(here!())
+}
+
+fn peek(reader: impl Read) -> Result<Header> {
+    serde_json::from_reader(reader)
+        .context("Failed to find `format_version` in rustdoc JSON output.")
+        .context(here!())
+}
+
+/// The part of rustdoc JSON output that's needed before the rest of it can be loaded. Every
+/// other field is skipped over without being deserialized.
+#[derive(Deserialize)]
+struct Header {
+    format_version: u32,
+}
+
+/// The crate ID of each entry in the `index` and `paths` of output in a format version where
+/// item IDs don't include it.
+#[derive(Deserialize)]
+struct ItemCrateIds {
+    #[serde(default)]
+    index: CrateIds,
+    #[serde(default)]
+    paths: CrateIds,
+}
+
+/// Crate ID of each item, for format versions where item IDs are integers that don't
+/// include it. Entries with other IDs are skipped without being kept in memory.
+#[derive(Default)]
+struct CrateIds(HashMap<u64, u64>);
+
+impl<'de> Deserialize<'de> for CrateIds {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(CrateIdsVisitor)
+    }
+}
+
+struct CrateIdsVisitor;
+
+impl<'de> de::Visitor<'de> for CrateIdsVisitor {
+    type Value = CrateIds;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map of items")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<CrateIds, A::Error> {
+        #[derive(Deserialize)]
+        struct Entry {
+            crate_id: Option<u64>,
+        }
+
+        let mut crate_ids = HashMap::new();
+        while let Some(id) = map.next_key::<String>()? {
+            match id.parse::<u64>() {
+                Ok(id) => {
+                    if let Some(crate_id) = map.next_value::<Entry>()?.crate_id {
+                        crate_ids.insert(id, crate_id);
+                    }
+                }
+                Err(_) => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(CrateIds(crate_ids))
+    }
+}
+
+/// How the output is converted into the current format version while it's deserialized.
+enum Format {
+    Current,
+    V57(v57::Migration),
+}
+
+impl Format {
+    /// Deserializes the next value of the map as an ID.
+    fn id<'de, A: MapAccess<'de>>(&self, map: &mut A) -> Result<Id, A::Error> {
+        match self {
+            Self::Current => map.next_value(),
+            Self::V57(migration) => {
+                let mut id: Value = map.next_value()?;
+                migration.id(&mut id).map_err(de::Error::custom)?;
+                serde_json::from_value(id).map_err(de::Error::custom)
+            }
+        }
+    }
+}
+
+/// Entry of the `index` or `paths` map.
+trait Entry: Sized {
+    /// Deserializes the value of the entry with the given ID in the given [`Format`]. The value
+    /// is `None` if the entry isn't needed.
+    fn next<'de, A: MapAccess<'de>>(
+        format: &Format,
+        map: &mut A,
+        id: String,
+    ) -> Result<(Id, Option<Self>), A::Error>;
+}
+
+impl Entry for Item {
+    fn next<'de, A: MapAccess<'de>>(
+        format: &Format,
+        map: &mut A,
+        id: String,
+    ) -> Result<(Id, Option<Self>), A::Error> {
+        let (id, mut item) = match format {
+            Format::Current => (Id(id), map.next_value::<Item>()?),
+            Format::V57(migration) => {
+                let mut item: Value = map.next_value()?;
+                migration.item(&mut item).map_err(|err| {
+                    de::Error::custom(format!("failed to migrate item {id}: {err:#}"))
+                })?;
+                let item = serde_json::from_value(item).map_err(de::Error::custom)?;
+                (Id(migration.id_key(&id).map_err(de::Error::custom)?), item)
+            }
+        };
+        if !is_visited(&item) {
+            return Ok((id, None));
+        }
+        // Docs make up much of the output of well documented crates, and the visitor
+        // never looks at them
+        item.docs = None;
+        item.links.clear();
+        Ok((id, Some(item)))
+    }
+}
+
+/// Returns whether the visitor can visit the given item. It ignores blanket impls, and items
+/// from other crates, which are only listed in the index of the root crate when they're items
+/// of blanket impls or re-exports (which it visits through the `Import` item instead).
+fn is_visited(item: &Item) -> bool {
+    // Rustdoc always gives the crate being documented ID 0
+    let in_root_crate = item.crate_id == 0;
+    let is_blanket_impl = matches!(&item.inner, ItemEnum::Impl(imp) if imp.blanket_impl.is_some());
+    in_root_crate && !is_blanket_impl
+}
+
+/// Removes the IDs of the given skipped entries from the lists of items and impls in the index,
+/// so that every ID in those lists can still be found in the index.
+fn remove_skipped(index: &mut HashMap<Id, Item>, skipped: &HashSet<Id>) {
+    if skipped.is_empty() {
+        return;
+    }
+    for item in index.values_mut() {
+        let ids = match &mut item.inner {
+            ItemEnum::Module(module) => &mut module.items,
+            ItemEnum::Struct(strct) => &mut strct.impls,
+            ItemEnum::Enum(enm) => &mut enm.impls,
+            ItemEnum::Union(unn) => &mut unn.impls,
+            ItemEnum::Trait(trt) => &mut trt.implementations,
+            ItemEnum::Primitive(primitive) => &mut primitive.impls,
+            _ => continue,
+        };
+        ids.retain(|id| !skipped.contains(id));
+    }
+}
+
+impl Entry for ItemSummary {
+    fn next<'de, A: MapAccess<'de>>(
+        format: &Format,
+        map: &mut A,
+        id: String,
+    ) -> Result<(Id, Option<Self>), A::Error> {
+        match format {
+            Format::Current => Ok((Id(id), Some(map.next_value()?))),
+            Format::V57(migration) => {
+                let mut summary: Value = map.next_value()?;
+                migration.summary(&mut summary).map_err(de::Error::custom)?;
+                Ok((
+                    Id(migration.id_key(&id).map_err(de::Error::custom)?),
+                    Some(serde_json::from_value(summary).map_err(de::Error::custom)?),
+                ))
+            }
+        }
+    }
+}
+
+/// Deserializes a whole [`Crate`] in the given [`Format`].
+struct CrateSeed<'a>(&'a Format);
+
+impl<'de> DeserializeSeed<'de> for CrateSeed<'_> {
+    type Value = Crate;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Crate, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> de::Visitor<'de> for CrateSeed<'_> {
+    type Value = Crate;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("rustdoc JSON output")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Crate, A::Error> {
+        let mut root = None;
+        let mut crate_version = None;
+        let mut includes_private = None;
+        let mut index = None;
+        let mut paths = None;
+        let mut external_crates = None;
+        let mut skipped = HashSet::new();
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "root" => root = Some(self.0.id(&mut map)?),
+                "crate_version" => crate_version = Some(map.next_value()?),
+                "includes_private" => includes_private = Some(map.next_value()?),
+                "index" => {
+                    index =
+                        Some(map.next_value_seed(EntriesSeed(self.0, &mut skipped, PhantomData))?)
+                }
+                "paths" => {
+                    paths =
+                        Some(map.next_value_seed(EntriesSeed(self.0, &mut skipped, PhantomData))?)
+                }
+                "external_crates" => external_crates = Some(map.next_value()?),
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        let mut index = index.ok_or_else(|| de::Error::missing_field("index"))?;
+        remove_skipped(&mut index, &skipped);
+        Ok(Crate {
+            root: root.ok_or_else(|| de::Error::missing_field("root"))?,
+            crate_version: crate_version
+                .ok_or_else(|| de::Error::missing_field("crate_version"))?,
+            includes_private: includes_private
+                .ok_or_else(|| de::Error::missing_field("includes_private"))?,
+            index,
+            paths: paths.ok_or_else(|| de::Error::missing_field("paths"))?,
+            external_crates: external_crates
+                .ok_or_else(|| de::Error::missing_field("external_crates"))?,
+            format_version: FORMAT_VERSION,
+        })
+    }
+}
+
+/// Deserializes the entries of the `index` or `paths` map one at a time, and adds the IDs of
+/// the entries that aren't needed to the given set.
+struct EntriesSeed<'a, T>(&'a Format, &'a mut HashSet<Id>, PhantomData<T>);
+
+impl<'de, T: Entry> DeserializeSeed<'de> for EntriesSeed<'_, T> {
+    type Value = HashMap<Id, T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, T: Entry> de::Visitor<'de> for EntriesSeed<'_, T> {
+    type Value = HashMap<Id, T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a map of items")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut entries = HashMap::with_capacity(map.size_hint().unwrap_or_default());
+        while let Some(id) = map.next_key::<String>()? {
+            match T::next(self.0, &mut map, id)? {
+                (id, Some(entry)) => {
+                    entries.insert(id, entry);
+                }
+                (id, None) => {
+                    self.1.insert(id);
+                }
+            }
+        }
+        Ok(entries)
+    }
+}
+
+/// Returns the supported format versions as a human readable list.
+pub fn supported_format_versions() -> String {
+    SUPPORTED_FORMAT_VERSIONS
+        .iter()
+        .map(|version| version.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn object(value: &mut Value) -> Result<&mut Map<String, Value>> {
+    match value {
+        Value::Object(map) => Ok(map),
+        other => Err(anyhow!("expected an object, found {other}")),
+    }
+}
+
+fn array(value: &mut Value) -> Result<&mut Vec<Value>> {
+    match value {
+        Value::Array(values) => Ok(values),
+        other => Err(anyhow!("expected an array, found {other}")),
+    }
+}
+
+fn field<'a>(map: &'a mut Map<String, Value>, name: &str) -> Result<&'a mut Value> {
+    map.get_mut(name)
+        .ok_or_else(|| anyhow!("missing field `{name}`"))
+}
+
+fn rename(map: &mut Map<String, Value>, from: &str, to: &str) -> Result<()> {
+    let value = map
+        .remove(from)
+        .ok_or_else(|| anyhow!("missing field `{from}`"))?;
+    map.insert(to.into(), value);
+    Ok(())
+}
+
+/// Returns the variant name and content of an externally tagged enum value. Unit variants
+/// are serialized as strings, and have no content.
+fn variant(value: &mut Value) -> Result<(&str, Option<&mut Value>)> {
+    match value {
+        Value::String(name) => Ok((name, None)),
+        Value::Object(map) if map.len() == 1 => {
+            let (name, content) = map.iter_mut().next().expect("len is 1");
+            Ok((name, Some(content)))
+        }
+        _ => Err(anyhow!("expected an enum variant")),
+    }
+}
+
+fn content<'a>(name: &str, content: Option<&'a mut Value>) -> Result<&'a mut Value> {
+    content.ok_or_else(|| anyhow!("expected `{name}` to have content"))
+}
+
+fn optional<T>(value: &mut Value, migrate: impl FnOnce(&mut Value) -> Result<T>) -> Result<()> {
+    if !value.is_null() {
+        migrate(value)?;
+    }
+    Ok(())
+}
+
+fn each(value: &mut Value, mut migrate: impl FnMut(&mut Value) -> Result<()>) -> Result<()> {
+    array(value)?.iter_mut().try_for_each(&mut migrate)
+}
+
+/// Format version 57, produced by nightlies from around the Rust 1.95 release.
+mod v57 {
+    use super::*;
+
+    pub(super) const FORMAT_VERSION: u32 = 57;
+
+    /// Returns the old name of item kinds that were renamed.
+    fn renamed_item_kind(kind: &str) -> Option<&'static str> {
+        match kind {
+            "use" => Some("import"),
+            "type_alias" => Some("typedef"),
+            "extern_type" => Some("foreign_type"),
+            // `#[doc(attribute = "...")]` pages are documentation-only, just like keywords
+            "attribute" => Some("keyword"),
+            _ => None,
+        }
+    }
+
+    fn empty_generic_args() -> Value {
+        json!({"angle_bracketed": {"args": [], "bindings": []}})
+    }
+
+    /// Converts an attribute, which may be structured, into its source form.
+    fn attribute(attr: &mut Value) -> Result<String> {
+        Ok(match variant(attr)? {
+            ("other", Some(Value::String(attr))) => attr.clone(),
+            ("must_use", Some(must_use)) => match must_use["reason"].as_str() {
+                Some(reason) => format!("#[must_use = {reason:?}]"),
+                None => "#[must_use]".into(),
+            },
+            ("repr", Some(repr)) => {
+                let mut args = Vec::new();
+                match repr["kind"].as_str() {
+                    Some("rust") | None => {}
+                    Some("c") => args.push("C".to_string()),
+                    Some(kind) => args.push(kind.to_string()),
+                }
+                if let Some(int) = repr["int"].as_str() {
+                    args.push(int.into());
+                }
+                if let Some(align) = repr["align"].as_u64() {
+                    args.push(format!("align({align})"));
+                }
+                if let Some(packed) = repr["packed"].as_u64() {
+                    args.push(format!("packed({packed})"));
+                }
+                format!("#[repr({})]", args.join(", "))
+            }
+            (name, None) => format!("#[{name}]"),
+            (name, Some(content)) => format!("#[{name}({content})]"),
+        })
+    }
+
+    /// Columns in spans are 1-based in this version instead of 0-based.
+    fn span(span: &mut Value) -> Result<()> {
+        let span = object(span)?;
+        for position in ["begin", "end"] {
+            let column = &mut array(field(span, position)?)?[1];
+            *column = json!(column.as_u64().unwrap_or_default().saturating_sub(1));
+        }
+        Ok(())
+    }
+
+    fn header(header: &mut Value) -> Result<()> {
+        let header = object(header)?;
+        rename(header, "is_const", "const")?;
+        rename(header, "is_unsafe", "unsafe")?;
+        rename(header, "is_async", "async")
+    }
+
+    /// Adds the type that constants had before it moved to the constant's item.
+    fn constant(constant: &mut Value) -> Result<()> {
+        object(constant)?.insert("type".into(), json!("infer"));
+        Ok(())
+    }
+
+    /// IDs are integers in this version, and are converted into strings prefixed with the
+    /// ID of the crate the item belongs to, since that is how the visitor tells whether an
+    /// item is from the root crate.
+    pub(super) struct Migration {
+        crate_ids: HashMap<u64, u64>,
+    }
+
+    impl Migration {
+        /// Creates a migration with the crate ID of each item in the `index` and `paths`.
+        pub(super) fn new(crate_ids: HashMap<u64, u64>) -> Self {
+            Self { crate_ids }
+        }
+
+        fn id_string(&self, id: u64) -> String {
+            // IDs that are neither in the index nor the paths are only expected in the root crate
+            let crate_id = self.crate_ids.get(&id).copied().unwrap_or(0);
+            format!("{crate_id}:{id}")
+        }
+
+        /// Converts an ID that's used as a map key.
+        pub(super) fn id_key(&self, id: &str) -> Result<String> {
+            let number = id
+                .parse()
+                .map_err(|_| anyhow!("expected an ID, found {id:?}"))?;
+            Ok(self.id_string(number))
+        }
+
+        pub(super) fn id(&self, id: &mut Value) -> Result<()> {
+            let number = id
+                .as_u64()
+                .ok_or_else(|| anyhow!("expected an ID, found {id}"))?;
+            *id = json!(self.id_string(number));
+            Ok(())
+        }
+
+        pub(super) fn summary(&self, summary: &mut Value) -> Result<()> {
+            let kind = field(object(summary)?, "kind")?;
+            if let Some(renamed) = kind.as_str().and_then(renamed_item_kind) {
+                *kind = json!(renamed);
+            }
+            Ok(())
+        }
+
+        fn ids(&self, ids: &mut Value) -> Result<()> {
+            each(ids, |id| self.id(id))
+        }
+
+        fn optional_ids(&self, ids: &mut Value) -> Result<()> {
+            each(ids, |id| optional(id, |id| self.id(id)))
+        }
+
+        pub(super) fn item(&self, item: &mut Value) -> Result<()> {
+            let item = object(item)?;
+            self.id(field(item, "id")?)?;
+            for link in object(field(item, "links")?)?.values_mut() {
+                self.id(link)?;
+            }
+            optional(field(item, "span")?, span)?;
+            each(field(item, "attrs")?, |attr| {
+                *attr = json!(attribute(attr)?);
+                Ok(())
+            })?;
+            self.visibility(field(item, "visibility")?)?;
+            self.inner(field(item, "inner")?)
+        }
+
+        fn visibility(&self, visibility: &mut Value) -> Result<()> {
+            if let ("restricted", Some(restricted)) = variant(visibility)? {
+                self.id(field(object(restricted)?, "parent")?)?;
+            }
+            Ok(())
+        }
+
+        fn inner(&self, inner: &mut Value) -> Result<()> {
+            let (name, value) = variant(inner)?;
+            let name = name.to_string();
+            let renamed = match name.as_str() {
+                "extern_crate" | "macro" | "proc_macro" => None,
+                "module" => {
+                    self.ids(field(object(content(&name, value)?)?, "items")?)?;
+                    None
+                }
+                "use" => {
+                    let import = object(content(&name, value)?)?;
+                    optional(field(import, "id")?, |id| self.id(id))?;
+                    rename(import, "is_glob", "glob")?;
+                    Some("import")
+                }
+                "union" => {
+                    let union = object(content(&name, value)?)?;
+                    self.generics(field(union, "generics")?)?;
+                    rename(union, "has_stripped_fields", "fields_stripped")?;
+                    self.ids(field(union, "fields")?)?;
+                    self.ids(field(union, "impls")?)?;
+                    None
+                }
+                "struct" => {
+                    let strukt = object(content(&name, value)?)?;
+                    self.fields(field(strukt, "kind")?)?;
+                    self.generics(field(strukt, "generics")?)?;
+                    self.ids(field(strukt, "impls")?)?;
+                    None
+                }
+                "struct_field" => {
+                    self.ty(content(&name, value)?)?;
+                    None
+                }
+                "enum" => {
+                    let enm = object(content(&name, value)?)?;
+                    self.generics(field(enm, "generics")?)?;
+                    rename(enm, "has_stripped_variants", "variants_stripped")?;
+                    self.ids(field(enm, "variants")?)?;
+                    self.ids(field(enm, "impls")?)?;
+                    None
+                }
+                "variant" => {
+                    self.fields(field(object(content(&name, value)?)?, "kind")?)?;
+                    None
+                }
+                "function" => {
+                    let function = object(content(&name, value)?)?;
+                    rename(function, "sig", "decl")?;
+                    self.signature(field(function, "decl")?)?;
+                    self.generics(field(function, "generics")?)?;
+                    header(field(function, "header")?)?;
+                    None
+                }
+                "trait" => {
+                    let trait_ = object(content(&name, value)?)?;
+                    self.ids(field(trait_, "items")?)?;
+                    self.generics(field(trait_, "generics")?)?;
+                    self.bounds(field(trait_, "bounds")?)?;
+                    self.ids(field(trait_, "implementations")?)?;
+                    None
+                }
+                "trait_alias" => {
+                    let alias = object(content(&name, value)?)?;
+                    self.generics(field(alias, "generics")?)?;
+                    self.bounds(field(alias, "params")?)?;
+                    None
+                }
+                "impl" => {
+                    let imp = object(content(&name, value)?)?;
+                    self.generics(field(imp, "generics")?)?;
+                    optional(field(imp, "trait")?, |path| self.path(path))?;
+                    self.ty(field(imp, "for")?)?;
+                    self.ids(field(imp, "items")?)?;
+                    rename(imp, "is_negative", "negative")?;
+                    rename(imp, "is_synthetic", "synthetic")?;
+                    optional(field(imp, "blanket_impl")?, |ty| self.ty(ty))?;
+                    None
+                }
+                "type_alias" => {
+                    let alias = object(content(&name, value)?)?;
+                    self.ty(field(alias, "type")?)?;
+                    self.generics(field(alias, "generics")?)?;
+                    Some("typedef")
+                }
+                "constant" => {
+                    let constant = object(content(&name, value)?)?;
+                    self.ty(field(constant, "type")?)?;
+                    // The expression moved into a nested `const` object
+                    let expr = constant
+                        .remove("const")
+                        .ok_or_else(|| anyhow!("missing field `const`"))?;
+                    if let Value::Object(expr) = expr {
+                        constant.extend(expr);
+                    }
+                    None
+                }
+                "static" => {
+                    let statik = object(content(&name, value)?)?;
+                    self.ty(field(statik, "type")?)?;
+                    rename(statik, "is_mutable", "mutable")?;
+                    None
+                }
+                "extern_type" => Some("foreign_type"),
+                "primitive" => {
+                    self.ids(field(object(content(&name, value)?)?, "impls")?)?;
+                    None
+                }
+                "assoc_const" => {
+                    let constant = object(content(&name, value)?)?;
+                    self.ty(field(constant, "type")?)?;
+                    rename(constant, "value", "default")?;
+                    None
+                }
+                "assoc_type" => {
+                    let assoc = object(content(&name, value)?)?;
+                    self.generics(field(assoc, "generics")?)?;
+                    self.bounds(field(assoc, "bounds")?)?;
+                    optional(field(assoc, "type")?, |ty| self.ty(ty))?;
+                    rename(assoc, "type", "default")?;
+                    None
+                }
+                other => bail!("unsupported item kind `{other}`"),
+            };
+            if let Some(renamed) = renamed {
+                match inner {
+                    Value::String(_) => *inner = json!(renamed),
+                    _ => rename(object(inner)?, &name, renamed)?,
+                }
+            }
+            Ok(())
+        }
+
+        /// Migrates the kind of a struct or variant.
+        fn fields(&self, kind: &mut Value) -> Result<()> {
+            match variant(kind)? {
+                ("tuple", Some(fields)) => self.optional_ids(fields),
+                ("plain" | "struct", Some(fields)) => {
+                    let fields = object(fields)?;
+                    rename(fields, "has_stripped_fields", "fields_stripped")?;
+                    self.ids(field(fields, "fields")?)
+                }
+                _ => Ok(()),
+            }
+        }
+
+        fn signature(&self, signature: &mut Value) -> Result<()> {
+            let signature = object(signature)?;
+            rename(signature, "is_c_variadic", "c_variadic")?;
+            each(field(signature, "inputs")?, |input| {
+                self.ty(&mut array(input)?[1])
+            })?;
+            optional(field(signature, "output")?, |ty| self.ty(ty))
+        }
+
+        fn generics(&self, generics: &mut Value) -> Result<()> {
+            let generics = object(generics)?;
+            self.generic_params(field(generics, "params")?)?;
+            each(field(generics, "where_predicates")?, |predicate| {
+                let (name, value) = variant(predicate)?;
+                match name {
+                    "bound_predicate" => {
+                        let bound = object(content(name, value)?)?;
+                        self.ty(field(bound, "type")?)?;
+                        self.bounds(field(bound, "bounds")?)?;
+                        self.generic_params(field(bound, "generic_params")?)
+                    }
+                    "lifetime_predicate" => {
+                        let lifetime = object(content(name, value)?)?;
+                        let outlives = lifetime
+                            .remove("outlives")
+                            .ok_or_else(|| anyhow!("missing field `outlives`"))?;
+                        let bounds = match outlives {
+                            Value::Array(outlives) => outlives
+                                .into_iter()
+                                .map(|lifetime| json!({ "outlives": lifetime }))
+                                .collect(),
+                            _ => Vec::new(),
+                        };
+                        lifetime.insert("bounds".into(), Value::Array(bounds));
+                        rename(object(predicate)?, "lifetime_predicate", "region_predicate")
+                    }
+                    "eq_predicate" => {
+                        let eq = object(content(name, value)?)?;
+                        self.ty(field(eq, "lhs")?)?;
+                        self.term(field(eq, "rhs")?)
+                    }
+                    other => bail!("unsupported where predicate `{other}`"),
+                }
+            })
+        }
+
+        fn generic_params(&self, params: &mut Value) -> Result<()> {
+            each(params, |param| {
+                match variant(field(object(param)?, "kind")?)? {
+                    ("type", Some(kind)) => {
+                        let kind = object(kind)?;
+                        self.bounds(field(kind, "bounds")?)?;
+                        optional(field(kind, "default")?, |ty| self.ty(ty))?;
+                        rename(kind, "is_synthetic", "synthetic")
+                    }
+                    ("const", Some(kind)) => self.ty(field(object(kind)?, "type")?),
+                    _ => Ok(()),
+                }
+            })
+        }
+
+        fn bounds(&self, bounds: &mut Value) -> Result<()> {
+            // Precise capturing (`use<'a, T>`) bounds didn't exist in the current version
+            array(bounds)?.retain(|bound| bound.get("use").is_none());
+            each(bounds, |bound| match variant(bound)? {
+                ("trait_bound", Some(bound)) => {
+                    let bound = object(bound)?;
+                    self.path(field(bound, "trait")?)?;
+                    self.generic_params(field(bound, "generic_params")?)
+                }
+                ("outlives", _) => Ok(()),
+                (other, _) => bail!("unsupported generic bound `{other}`"),
+            })
+        }
+
+        fn path(&self, path: &mut Value) -> Result<()> {
+            let path = object(path)?;
+            rename(path, "path", "name")?;
+            self.id(field(path, "id")?)?;
+            optional(field(path, "args")?, |args| self.generic_args(args))
+        }
+
+        fn generic_args(&self, args: &mut Value) -> Result<()> {
+            let (name, value) = variant(args)?;
+            match name {
+                "angle_bracketed" => {
+                    let angle = object(content(name, value)?)?;
+                    each(field(angle, "args")?, |arg| match variant(arg)? {
+                        ("type", Some(ty)) => self.ty(ty),
+                        ("const", Some(constant)) => self::constant(constant),
+                        _ => Ok(()),
+                    })?;
+                    rename(angle, "constraints", "bindings")?;
+                    each(field(angle, "bindings")?, |binding| {
+                        let binding = object(binding)?;
+                        let args = field(binding, "args")?;
+                        if args.is_null() {
+                            *args = empty_generic_args();
+                        } else {
+                            self.generic_args(args)?;
+                        }
+                        match variant(field(binding, "binding")?)? {
+                            ("equality", Some(term)) => self.term(term),
+                            ("constraint", Some(bounds)) => self.bounds(bounds),
+                            (other, _) => bail!("unsupported constraint `{other}`"),
+                        }
+                    })
+                }
+                "parenthesized" => {
+                    let parenthesized = object(content(name, value)?)?;
+                    each(field(parenthesized, "inputs")?, |ty| self.ty(ty))?;
+                    optional(field(parenthesized, "output")?, |ty| self.ty(ty))
+                }
+                // Return type notation (`T::method(..)`) didn't exist in the current version
+                "return_type_notation" => {
+                    *args = empty_generic_args();
+                    Ok(())
+                }
+                other => bail!("unsupported generic args `{other}`"),
+            }
+        }
+
+        fn term(&self, term: &mut Value) -> Result<()> {
+            match variant(term)? {
+                ("type", Some(ty)) => self.ty(ty),
+                ("constant", Some(constant)) => self::constant(constant),
+                (other, _) => bail!("unsupported term `{other}`"),
+            }
+        }
+
+        fn ty(&self, ty: &mut Value) -> Result<()> {
+            // Pattern types (`u32 is 1..`) didn't exist in the current version
+            if let Some(pat) = ty.get_mut("pat") {
+                let inner = field(object(pat)?, "type")?.take();
+                *ty = inner;
+                return self.ty(ty);
+            }
+            let (name, value) = variant(ty)?;
+            match name {
+                "generic" | "primitive" | "infer" => Ok(()),
+                "resolved_path" => self.path(content(name, value)?),
+                "dyn_trait" => each(field(object(content(name, value)?)?, "traits")?, |poly| {
+                    let poly = object(poly)?;
+                    self.path(field(poly, "trait")?)?;
+                    self.generic_params(field(poly, "generic_params")?)
+                }),
+                "function_pointer" => {
+                    let pointer = object(content(name, value)?)?;
+                    rename(pointer, "sig", "decl")?;
+                    self.signature(field(pointer, "decl")?)?;
+                    self.generic_params(field(pointer, "generic_params")?)?;
+                    header(field(pointer, "header")?)
+                }
+                "tuple" => each(content(name, value)?, |ty| self.ty(ty)),
+                "slice" => self.ty(content(name, value)?),
+                "array" => self.ty(field(object(content(name, value)?)?, "type")?),
+                "impl_trait" => self.bounds(content(name, value)?),
+                "raw_pointer" | "borrowed_ref" => {
+                    let pointer = object(content(name, value)?)?;
+                    rename(pointer, "is_mutable", "mutable")?;
+                    self.ty(field(pointer, "type")?)
+                }
+                "qualified_path" => {
+                    let qualified = object(content(name, value)?)?;
+                    let args = field(qualified, "args")?;
+                    if args.is_null() {
+                        *args = empty_generic_args();
+                    } else {
+                        self.generic_args(args)?;
+                    }
+                    self.ty(field(qualified, "self_type")?)?;
+                    optional(field(qualified, "trait")?, |path| self.path(path))
+                }
+                other => bail!("unsupported type `{other}`"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_crate, peek};
+    use rustdoc_types::{Id, ItemEnum, ItemKind, Type, FORMAT_VERSION};
+    use std::collections::HashSet;
+
+    // Trimmed down from the output of `pub struct S; pub use external::f;`, where `external::f`
+    // is inlined, and `S` has a blanket impl from `external`
+    const V26: &str = r##"{
+        "root": "0:0",
+        "crate_version": null,
+        "includes_private": true,
+        "index": {
+            "0:0": {
+                "id": "0:0", "crate_id": 0, "name": "krate", "span": null,
+                "visibility": "public", "docs": null, "links": {}, "attrs": [], "deprecation": null,
+                "inner": {"module": {"is_crate": true, "items": ["0:1", "1:5"], "is_stripped": false}}
+            },
+            "0:1": {
+                "id": "0:1", "crate_id": 0, "name": "S", "span": null,
+                "visibility": "public", "docs": "Docs", "links": {}, "attrs": [], "deprecation": null,
+                "inner": {"struct": {
+                    "kind": "unit",
+                    "generics": {"params": [], "where_predicates": []},
+                    "impls": ["b:1:2-0:1"]
+                }}
+            },
+            "b:1:2-0:1": {
+                "id": "b:1:2-0:1", "crate_id": 0, "name": null, "span": null,
+                "visibility": "default", "docs": null, "links": {}, "attrs": [], "deprecation": null,
+                "inner": {"impl": {
+                    "is_unsafe": false,
+                    "generics": {"params": [], "where_predicates": []},
+                    "provided_trait_methods": [],
+                    "trait": {"name": "Blanket", "id": "1:2", "args": null},
+                    "for": {"generic": "T"},
+                    "items": ["1:3"],
+                    "negative": false,
+                    "synthetic": false,
+                    "blanket_impl": {"generic": "T"}
+                }}
+            },
+            "1:3": {
+                "id": "1:3", "crate_id": 1, "name": "g", "span": null,
+                "visibility": "public", "docs": null, "links": {}, "attrs": [], "deprecation": null,
+                "inner": {"function": {
+                    "decl": {"inputs": [], "output": null, "c_variadic": false},
+                    "generics": {"params": [], "where_predicates": []},
+                    "header": {"const": false, "unsafe": false, "async": false, "abi": "Rust"},
+                    "has_body": true
+                }}
+            },
+            "1:5": {
+                "id": "1:5", "crate_id": 1, "name": "f", "span": null,
+                "visibility": "public", "docs": null, "links": {}, "attrs": [], "deprecation": null,
+                "inner": {"function": {
+                    "decl": {"inputs": [], "output": null, "c_variadic": false},
+                    "generics": {"params": [], "where_predicates": []},
+                    "header": {"const": false, "unsafe": false, "async": false, "abi": "Rust"},
+                    "has_body": true
+                }}
+            }
+        },
+        "paths": {
+            "0:1": {"crate_id": 0, "path": ["krate", "S"], "kind": "struct"},
+            "1:2": {"crate_id": 1, "path": ["external", "Blanket"], "kind": "trait"}
+        },
+        "external_crates": {"1": {"name": "external", "html_root_url": null}},
+        "format_version": 26
+    }"##;
+
+    // Trimmed down from the output of `pub fn f(x: &mut external::Thing) {}`
+    const V57: &str = r##"{
+        "root": 0,
+        "crate_version": null,
+        "includes_private": false,
+        "index": {
+            "0": {
+                "id": 0, "crate_id": 0, "name": "krate",
+                "span": {"filename": "src/lib.rs", "begin": [1, 1], "end": [1, 35]},
+                "visibility": "public", "docs": null, "links": {}, "attrs": [], "deprecation": null,
+                "inner": {"module": {"is_crate": true, "items": [1], "is_stripped": false}}
+            },
+            "1": {
+                "id": 1, "crate_id": 0, "name": "f",
+                "span": {"filename": "src/lib.rs", "begin": [1, 1], "end": [1, 35]},
+                "visibility": "public", "docs": null, "links": {},
+                "attrs": ["non_exhaustive", {"other": "#[allow(dead_code)]"}], "deprecation": null,
+                "inner": {"function": {
+                    "sig": {
+                        "inputs": [["x", {"borrowed_ref": {"lifetime": null, "is_mutable": true,
+                            "type": {"resolved_path": {"path": "external::Thing", "id": 2, "args": null}}}}]],
+                        "output": null,
+                        "is_c_variadic": false
+                    },
+                    "generics": {"params": [], "where_predicates": []},
+                    "header": {"is_const": false, "is_unsafe": false, "is_async": false, "abi": "Rust"},
+                    "has_body": true
+                }}
+            }
+        },
+        "paths": {
+            "0": {"crate_id": 0, "path": ["krate"], "kind": "module"},
+            "2": {"crate_id": 1, "path": ["external", "Thing"], "kind": "type_alias"}
+        },
+        "external_crates": {"1": {"name": "external", "html_root_url": null, "path": "external.rlib"}},
+        "target": {"triple": "x86_64-unknown-linux-gnu", "target_features": []},
+        "format_version": 57
+    }"##;
+
+    #[test]
+    fn migrate_v57() {
+        let package = parse_crate(V57).unwrap();
+        assert_eq!(FORMAT_VERSION, package.format_version);
+        assert_eq!(Id("0:0".into()), package.root);
+        assert_eq!(ItemKind::Typedef, package.paths[&Id("1:2".into())].kind);
+
+        let function = &package.index[&Id("0:1".into())];
+        assert_eq!((1, 0), function.span.as_ref().unwrap().begin);
+        assert_eq!(
+            vec!["#[non_exhaustive]", "#[allow(dead_code)]"],
+            function.attrs
+        );
+        let ItemEnum::Function(function) = &function.inner else {
+            panic!("expected a function");
+        };
+        let Type::BorrowedRef { mutable, type_, .. } = &function.decl.inputs[0].1 else {
+            panic!("expected a reference");
+        };
+        assert!(mutable);
+        let Type::ResolvedPath(path) = type_.as_ref() else {
+            panic!("expected a path");
+        };
+        assert_eq!("external::Thing", path.name);
+        assert_eq!(Id("1:2".into()), path.id);
+    }
+
+    #[test]
+    fn skips_entries_that_are_never_visited() {
+        let package = parse_crate(V26).unwrap();
+        assert_eq!(
+            HashSet::from([&Id("0:0".into()), &Id("0:1".into())]),
+            package.index.keys().collect::<HashSet<_>>()
+        );
+        // The references to skipped entries are removed too
+        let ItemEnum::Module(module) = &package.index[&package.root].inner else {
+            panic!("expected a module");
+        };
+        assert_eq!(vec![Id("0:1".into())], module.items);
+        let strct = &package.index[&Id("0:1".into())];
+        assert_eq!(None, strct.docs);
+        let ItemEnum::Struct(strct) = &strct.inner else {
+            panic!("expected a struct");
+        };
+        assert!(strct.impls.is_empty());
+        // Paths are kept, since they're how the visitor names external types
+        assert_eq!(2, package.paths.len());
+    }
+
+    #[test]
+    fn peek_only_reads_the_format_version() {
+        // The index isn't even a map, but it's never deserialized
+        let header = peek(r#"{"index": [1, 2], "paths": 3, "format_version": 26}"#.as_bytes());
+        assert_eq!(26, header.unwrap().format_version);
+    }
+
+    #[test]
+    fn unsupported_format_version() {
+        let error = parse_crate(r#"{"format_version": 1}"#).unwrap_err();
+        assert!(
+            format!("{error:?}").contains("produces JSON format version 1"),
+            "{error:?}"
+        );
+    }
+}
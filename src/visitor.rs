@@ -3,9 +3,12 @@
  * SPDX-License-Identifier: Apache-2.0
  */
 
+use crate::cargo::CrateVersion;
 use crate::config::Config;
-use crate::error::{ErrorLocation, ValidationError, ValidationErrors};
-use crate::path::{ComponentType, Path};
+use crate::error::{
+    location_sort_key, CrateUsage, ErrorLocation, TypePosition, ValidationError, ValidationErrors,
+};
+use crate::path::{ComponentType, Path, PathSuffix};
 use crate::{bug_panic, here};
 use anyhow::{anyhow, Context, Result};
 use rustdoc_types::{
@@ -14,7 +17,8 @@ use rustdoc_types::{
     Variant, VariantKind, Visibility, WherePredicate,
 };
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
 use std::sync::Once;
 use tracing::{debug, warn};
 use tracing_attributes::instrument;
@@ -28,7 +32,7 @@ macro_rules! unstable_rust_feature {
     };
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
 enum VisibilityCheck {
     /// Check to make sure the item is public before visiting it
     Default,
@@ -40,6 +44,92 @@ enum VisibilityCheck {
 pub(crate) type Index = HashMap<Id, Item>;
 pub(crate) type Paths = HashMap<Id, ItemSummary>;
 
+/// Something found in an item that contributes to the results, such as a reference to a type.
+#[derive(Clone, Debug)]
+enum Finding {
+    /// A reference to the type with the given ID (see [`Visitor::check_external`])
+    TypeRef {
+        what: ErrorLocation,
+        chain: Vec<TypePosition>,
+        id: Id,
+        /// Path to the type as it's written in the source
+        written_path: String,
+    },
+    /// A struct whose fields were stripped from the documentation
+    FieldsStripped,
+    /// A re-export of an item that's declared in a `#[doc(hidden)]` module
+    HiddenModule {
+        name: String,
+        hidden_module: Option<String>,
+    },
+}
+
+/// A [`Finding`] along with where it was found, relative to the path of the item it's memoized for.
+#[derive(Clone, Debug)]
+struct RelativeFinding {
+    path: PathSuffix,
+    finding: Finding,
+}
+
+/// The findings in an item only depend on the item, the visibility check, and the type of the
+/// last component of the path that the item was reached through (see [`Visitor::is_public`]).
+type MemoKey = (Id, VisibilityCheck, Option<ComponentType>);
+
+/// Findings that are being recorded while an item is visited, so that they can be memoized.
+struct Recording {
+    key: MemoKey,
+    /// Depth of the path that the item was reached through
+    depth: usize,
+    findings: Vec<RelativeFinding>,
+    /// False if a cyclic re-export was cut short while visiting the item
+    complete: bool,
+}
+
+/// References to types from a specific external crate ID.
+struct ExternalCrateRef {
+    /// Sort key of the first reference, in error order
+    sort_key: String,
+    /// The first reference
+    usage: CrateUsage,
+    /// First segments of the paths that the crate's types are written with in the source, which
+    /// include the name that the root crate imports the crate as, if it's a direct dependency
+    written_crate_names: BTreeSet<String>,
+}
+
+/// Works out which of the `versions` of a crate each of its crate IDs is: first by the names
+/// that the crate's types are written with in the source, and then by elimination if that
+/// leaves only one crate ID and one version.
+fn match_crate_versions<'a>(
+    refs: &BTreeMap<u32, ExternalCrateRef>,
+    versions: &'a [CrateVersion],
+) -> BTreeMap<u32, &'a CrateVersion> {
+    let mut matched = BTreeMap::new();
+    for (&crate_id, crate_ref) in refs {
+        let mut candidates = versions.iter().filter(|version| {
+            version
+                .extern_names
+                .iter()
+                .any(|name| crate_ref.written_crate_names.contains(name))
+        });
+        if let (Some(version), None) = (candidates.next(), candidates.next()) {
+            matched.insert(crate_id, version);
+        }
+    }
+    let unmatched_ids: Vec<u32> = refs
+        .keys()
+        .filter(|crate_id| !matched.contains_key(crate_id))
+        .copied()
+        .collect();
+    let unmatched_versions: Vec<&CrateVersion> = versions
+        .iter()
+        .filter(|version| !matched.values().any(|matched| matched == version))
+        .collect();
+    if let ([crate_id], [version]) = (&unmatched_ids[..], &unmatched_versions[..]) {
+        matched.insert(*crate_id, *version);
+    }
+    matched
+}
+
 /// Visits all items in the Rustdoc JSON output to discover external types in public APIs
 /// and track them as validation errors if the [`Config`] doesn't allow them.
 pub struct Visitor {
@@ -53,12 +143,33 @@ pub struct Visitor {
     index: Index,
     /// Map of rustdoc [`Id`] to rustdoc [`ItemSummary`]
     paths: Paths,
+    /// Versions of each crate in the dependency graph, keyed by crate name
+    crate_versions: BTreeMap<String, Vec<CrateVersion>>,
 
+    /// First reference to each external crate, keyed by crate name and then rustdoc crate ID
+    ///
+    /// Two versions of the same crate have the same name but different crate IDs.
+    external_crate_refs: RefCell<BTreeMap<String, BTreeMap<u32, ExternalCrateRef>>>,
     /// Set of errors
     ///
     /// The visitor adds errors to this set while it visits each item in the rustdoc
     /// output.
     errors: RefCell<ValidationErrors>,
+    /// Unapproved references to external types along with the public path they were found
+    /// through, keyed by the item they're in (see [`Path::item_key`]) and the reference
+    ///
+    /// Items can be reachable through more than one public path, and these are only added to
+    /// `errors` once all of the paths are known.
+    unapproved_refs: RefCell<BTreeMap<String, Vec<(String, ValidationError)>>>,
+
+    /// Findings in each item that has been visited, so that items that are reachable through
+    /// more than one path (for example, through re-exports) are only visited once
+    memo: RefCell<HashMap<MemoKey, Rc<[RelativeFinding]>>>,
+    /// Findings being recorded for each item that is currently being visited, from outermost
+    /// to innermost
+    recordings: RefCell<Vec<Recording>>,
+    /// Items that are currently being visited, so that cyclic re-exports don't recurse forever
+    in_progress: RefCell<HashSet<MemoKey>>,
 }
 
 impl Visitor {
@@ -69,10 +180,27 @@ impl Visitor {
             root_crate_name: Self::root_crate_name(&package)?,
             index: package.index,
             paths: package.paths,
+            crate_versions: BTreeMap::new(),
+            external_crate_refs: RefCell::new(BTreeMap::new()),
             errors: RefCell::new(ValidationErrors::new()),
+            unapproved_refs: RefCell::new(BTreeMap::new()),
+            memo: RefCell::new(HashMap::new()),
+            recordings: RefCell::new(Vec::new()),
+            in_progress: RefCell::new(HashSet::new()),
         })
     }
 
+    /// Sets the versions of each crate in the dependency graph (as returned by
+    /// [`dependency_versions`](crate::cargo::dependency_versions)) so that errors about
+    /// multiple versions of a crate can say which versions are involved.
+    pub fn with_crate_versions(
+        mut self,
+        crate_versions: BTreeMap<String, Vec<CrateVersion>>,
+    ) -> Self {
+        self.crate_versions = crate_versions;
+        self
+    }
+
     /// This is the entry point for visiting the entire Rustdoc JSON tree, starting
     /// from the root module (the only module where `is_crate` is true).
     pub fn visit_all(self) -> Result<ValidationErrors> {
@@ -94,9 +222,60 @@ impl Visitor {
             let item = self.item(id).context(here!())?;
             self.visit_item(&root_path, item, VisibilityCheck::Default)?;
         }
+        self.add_unapproved_refs();
+        self.check_duplicate_crate_versions();
         Ok(self.errors.take())
     }
 
+    /// Adds an error for each unapproved reference, no matter how many public paths the item it's
+    /// in is reachable through. The item is named by its shortest path, like in the docs, and
+    /// the other paths are listed in the error.
+    fn add_unapproved_refs(&self) {
+        for (_, mut refs) in self.unapproved_refs.take() {
+            refs.sort_by(|(a, _), (b, _)| {
+                (a.matches("::").count(), a.len(), a).cmp(&(b.matches("::").count(), b.len(), b))
+            });
+            refs.dedup_by(|(a, _), (b, _)| a == b);
+            let mut refs = refs.into_iter();
+            let (_, error) = refs.next().expect("at least one reference per key");
+            let other_paths = refs.map(|(path, _)| path).collect();
+            self.add_error(error.with_other_paths(other_paths));
+        }
+    }
+
+    /// Adds an error for every external crate name that was referenced through more than
+    /// one crate ID, which means that multiple versions of that crate are in the public API.
+    fn check_duplicate_crate_versions(&self) {
+        for (crate_name, refs) in self.external_crate_refs.borrow().iter() {
+            if refs.len() < 2 {
+                continue;
+            }
+            let versions = self
+                .crate_versions
+                .get(crate_name)
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let matched = match_crate_versions(refs, versions);
+            let mut refs: Vec<(&u32, &ExternalCrateRef)> = refs.iter().collect();
+            refs.sort_by(|(_, a), (_, b)| a.sort_key.cmp(&b.sort_key));
+            let usages = refs
+                .into_iter()
+                .map(|(crate_id, crate_ref)| CrateUsage {
+                    version: matched.get(crate_id).map(ToString::to_string),
+                    ..crate_ref.usage.clone()
+                })
+                .collect();
+            self.add_error(ValidationError::duplicate_crate_versions(
+                crate_name,
+                versions
+                    .iter()
+                    .map(|version| version.version.clone())
+                    .collect(),
+                usages,
+            ));
+        }
+    }
+
     /// Returns true if the given item is public. In some cases, this must be determined
     /// by examining the surrounding context. For example, enum variants are public if the
     /// enum is public, even if their visibility is set to `Visibility::Default`.
@@ -120,12 +299,61 @@ impl Visitor {
         }
     }
 
+    /// Visits an item, or replays what was found in it at the given path if it has been visited
+    /// before.
     #[instrument(level = "debug", skip(self, path, item), fields(path = %path, name = ?item.name, id = %item.id.0))]
     fn visit_item(
         &self,
         path: &Path,
         item: &Item,
         visibility_check: VisibilityCheck,
+    ) -> Result<()> {
+        let key = (item.id.clone(), visibility_check, path.last_type());
+        let memoized = self.memo.borrow().get(&key).cloned();
+        if let Some(findings) = memoized {
+            for relative in findings.iter() {
+                self.found(&path.join(&relative.path), relative.finding.clone())?;
+            }
+            return Ok(());
+        }
+        if !self.in_progress.borrow_mut().insert(key.clone()) {
+            // A cyclic re-export led back to an item that is already being visited. Everything in
+            // it is found through the path it was first reached through, but the items visited
+            // since then are missing whatever the cycle would have found through them.
+            let mut recordings = self.recordings.borrow_mut();
+            let start = recordings
+                .iter()
+                .position(|recording| recording.key == key)
+                .expect("items in progress are being recorded");
+            for recording in &mut recordings[start + 1..] {
+                recording.complete = false;
+            }
+            return Ok(());
+        }
+
+        self.recordings.borrow_mut().push(Recording {
+            key: key.clone(),
+            depth: path.depth(),
+            findings: Vec::new(),
+            complete: true,
+        });
+        let result = self.visit_item_contents(path, item, visibility_check);
+        let recording = self.recordings.borrow_mut().pop().expect("pushed above");
+        self.in_progress.borrow_mut().remove(&key);
+        result?;
+        if recording.complete {
+            self.memo
+                .borrow_mut()
+                .insert(key, recording.findings.into());
+        }
+        Ok(())
+    }
+
+    fn visit_item_contents(
+        &self,
+        path: &Path,
+        item: &Item,
+        visibility_check: VisibilityCheck,
     ) -> Result<()> {
         if visibility_check == VisibilityCheck::Default && !Self::is_public(path, item) {
             return Ok(());
@@ -135,7 +363,7 @@ impl Visitor {
         match &item.inner {
             ItemEnum::AssocConst { type_, .. } => {
                 path.push(ComponentType::AssocConst, item);
-                self.visit_type(&path, &ErrorLocation::StructField, type_)
+                self.visit_type(&path, &ErrorLocation::StructField, &[], type_)
                     .context(here!())?;
             }
             ItemEnum::AssocType {
@@ -145,14 +373,15 @@ impl Visitor {
             } => {
                 path.push(ComponentType::AssocType, item);
                 if let Some(typ) = default {
-                    self.visit_type(&path, &ErrorLocation::AssocType, typ).context(here!())?;
+                    self.visit_type(&path, &ErrorLocation::AssocType, &[], typ).context(here!())?;
                 }
-                self.visit_generic_bounds(&path, bounds).context(here!())?;
+                self.visit_generic_bounds(&path, &ErrorLocation::TraitBound, &[], bounds)
+                    .context(here!())?;
                 self.visit_generics(&path, generics).context(here!())?;
             }
             ItemEnum::Constant(constant) => {
                 path.push(ComponentType::Constant, item);
-                self.visit_type(&path, &ErrorLocation::Constant, &constant.type_).context(here!())?;
+                self.visit_type(&path, &ErrorLocation::Constant, &[], &constant.type_).context(here!())?;
             }
             ItemEnum::Enum(enm) => {
                 path.push(ComponentType::Enum, item);
@@ -188,24 +417,32 @@ impl Visitor {
                             // the user that we couldn't check this type for external types.
                             Err(_) => {
                                 update_path.call_once(|| {
-                                    path.push_raw(ComponentType::ReExport, &import.name, item.span.as_ref());
+                                    path.push_raw(ComponentType::ReExport, &import.name, Some(&item.id), item.span.as_ref());
                                 });
                                 let first_hidden_module_in_path = infer_first_hidden_module_in_import_source(&import.source, &self.index);
-                                self.add_error(ValidationError::hidden_module(
-                                    import.name.clone(),
-                                    &ErrorLocation::ReExport,
-                                    path.to_string(),
-                                    path.last_span(),
-                                    first_hidden_module_in_path,
-                                ));
+                                self.found(
+                                    &path,
+                                    Finding::HiddenModule {
+                                        name: import.name.clone(),
+                                        hidden_module: first_hidden_module_in_path,
+                                    },
+                                )?;
                             }
                         };
                     }
                     update_path.call_once(|| {
-                        path.push_raw(ComponentType::ReExport, &import.name, item.span.as_ref());
+                        path.push_raw(ComponentType::ReExport, &import.name, Some(&item.id), item.span.as_ref());
                     });
-                    self.check_external(&path, &ErrorLocation::ReExport, target_id)
-                        .context(here!())?;
+                    self.found(
+                        &path,
+                        Finding::TypeRef {
+                            what: ErrorLocation::ReExport,
+                            chain: Vec::new(),
+                            id: target_id.clone(),
+                            written_path: import.source.clone(),
+                        },
+                    )
+                    .context(here!())?;
                 }
             }
             ItemEnum::Module(module) => {
@@ -227,7 +464,7 @@ impl Visitor {
             ItemEnum::OpaqueTy(_) => unstable_rust_feature!("type_alias_impl_trait", "https://doc.rust-lang.org/beta/unstable-book/language-features/type-alias-impl-trait.html"),
             ItemEnum::Static(sttc) => {
                 path.push(ComponentType::Static, item);
-                self.visit_type(&path, &ErrorLocation::Static, &sttc.type_).context(here!())?;
+                self.visit_type(&path, &ErrorLocation::Static, &[], &sttc.type_).context(here!())?;
             }
             ItemEnum::Struct(strct) => {
                 path.push(ComponentType::Struct, item);
@@ -235,7 +472,7 @@ impl Visitor {
             }
             ItemEnum::StructField(typ) => {
                 path.push(ComponentType::StructField, item);
-                self.visit_type(&path, &ErrorLocation::StructField, typ)
+                self.visit_type(&path, &ErrorLocation::StructField, &[], typ)
                     .context(here!())?;
             }
             ItemEnum::Trait(trt) => {
@@ -244,7 +481,7 @@ impl Visitor {
             }
             ItemEnum::Typedef(typedef) => {
                 path.push(ComponentType::TypeDef, item);
-                self.visit_type(&path, &ErrorLocation::TypeDef, &typedef.type_)
+                self.visit_type(&path, &ErrorLocation::TypeDef, &[], &typedef.type_)
                     .context(here!())?;
                 self.visit_generics(&path, &typedef.generics).context(here!())?;
             }
@@ -276,6 +513,7 @@ impl Visitor {
             impl_path.push_raw(
                 ComponentType::Impl,
                 "",
+                Some(&impl_item.id),
                 impl_item.span.as_ref().or_else(|| path.last_span()),
             );
             self.visit_impl(&impl_path, impl_item).context(here!())?;
@@ -297,7 +535,7 @@ impl Visitor {
                 fields_stripped,
             } => {
                 if *fields_stripped {
-                    self.add_error(ValidationError::fields_stripped(path));
+                    self.found(path, Finding::FieldsStripped)?;
                 }
                 fields.clone()
             }
@@ -324,7 +562,7 @@ impl Visitor {
     #[instrument(level = "debug", skip(self, path, trt), fields(path = %path))]
     fn visit_trait(&self, path: &Path, trt: &Trait) -> Result<()> {
         self.visit_generics(path, &trt.generics)?;
-        self.visit_generic_bounds(path, &trt.bounds)?;
+        self.visit_generic_bounds(path, &ErrorLocation::TraitBound, &[], &trt.bounds)?;
         for id in &trt.items {
             let item = self.item(id).context(here!())?;
             self.visit_item(path, item, VisibilityCheck::Default)?;
@@ -357,7 +595,7 @@ impl Visitor {
                     }
                 }
 
-                self.check_rustdoc_path(path, &ErrorLocation::ImplementedTrait, trait_)
+                self.check_rustdoc_path(path, &ErrorLocation::ImplementedTrait, &[], trait_)
                     .context(here!())?;
             }
 
@@ -381,42 +619,60 @@ impl Visitor {
             if index == 0 && name == "self" {
                 continue;
             }
-            self.visit_type(path, &ErrorLocation::ArgumentNamed(name.into()), typ)
+            self.visit_type(path, &ErrorLocation::ArgumentNamed(name.into()), &[], typ)
                 .context(here!())?;
         }
         if let Some(output) = &decl.output {
-            self.visit_type(path, &ErrorLocation::ReturnValue, output)
+            self.visit_type(path, &ErrorLocation::ReturnValue, &[], output)
                 .context(here!())?;
         }
         Ok(())
     }
 
-    #[instrument(level = "debug", skip(self, path, typ), fields(path = %path))]
-    fn visit_type(&self, path: &Path, what: &ErrorLocation, typ: &Type) -> Result<()> {
+    /// Visits a type at the given location, where `chain` is the position of the type within
+    /// the outermost type at that location.
+    #[instrument(level = "debug", skip(self, path, chain, typ), fields(path = %path))]
+    fn visit_type(
+        &self,
+        path: &Path,
+        what: &ErrorLocation,
+        chain: &[TypePosition],
+        typ: &Type,
+    ) -> Result<()> {
         match typ {
             Type::ResolvedPath(resolved_path) => {
-                self.check_rustdoc_path(path, what, resolved_path)
+                self.check_rustdoc_path(path, what, chain, resolved_path)
                     .context(here!())?;
-                if let Some(args) = &resolved_path.args {
-                    self.visit_generic_args(path, args.as_ref())?;
-                }
             }
             Type::Generic(_) => {}
             Type::Primitive(_) => {}
             Type::FunctionPointer(fp) => {
-                self.visit_fn_decl(path, &fp.decl)?;
+                for (index, (_, typ)) in fp.decl.inputs.iter().enumerate() {
+                    let of = "fn".to_string();
+                    let chain = nested(chain, TypePosition::FnInput { index, of });
+                    self.visit_type(path, what, &chain, typ)?;
+                }
+                if let Some(output) = &fp.decl.output {
+                    let of = "fn".to_string();
+                    let chain = nested(chain, TypePosition::FnOutput { of });
+                    self.visit_type(path, what, &chain, output)?;
+                }
                 self.visit_generic_param_defs(path, &fp.generic_params)?;
             }
             Type::Tuple(types) => {
-                for typ in types {
-                    self.visit_type(path, &ErrorLocation::EnumTupleEntry, typ)?;
+                for (index, typ) in types.iter().enumerate() {
+                    let chain = nested(chain, TypePosition::TupleEntry(index));
+                    self.visit_type(path, what, &chain, typ)?;
                 }
             }
-            Type::Slice(typ) => self.visit_type(path, what, typ).context(here!())?,
-            Type::Array { type_, .. } => self.visit_type(path, what, type_).context(here!())?,
+            Type::Slice(typ) => self.visit_type(path, what, chain, typ).context(here!())?,
+            Type::Array { type_, .. } => {
+                self.visit_type(path, what, chain, type_).context(here!())?
+            }
             Type::DynTrait(dyn_trait) => {
+                let chain = nested(chain, TypePosition::TraitObject);
                 for trait_ in &dyn_trait.traits {
-                    self.check_rustdoc_path(path, &ErrorLocation::DynTrait, &trait_.trait_)
+                    self.check_rustdoc_path(path, what, &chain, &trait_.trait_)
                         .context(here!())?;
                     self.visit_generic_param_defs(path, &trait_.generic_params)
                         .context(here!())?;
@@ -430,7 +686,7 @@ impl Visitor {
                             generic_params,
                             ..
                         } => {
-                            self.check_rustdoc_path(path, what, trait_)?;
+                            self.check_rustdoc_path(path, what, chain, trait_)?;
                             self.visit_generic_param_defs(path, generic_params)?;
                         }
                         GenericBound::Outlives(_) => {}
@@ -442,35 +698,43 @@ impl Visitor {
                 bug_panic!("This is a bug (visit_type for Type::Infer).");
             }
             Type::RawPointer { type_, .. } => {
-                self.visit_type(path, what, type_).context(here!())?
+                self.visit_type(path, what, chain, type_).context(here!())?
             }
             Type::BorrowedRef { type_, .. } => {
-                self.visit_type(path, what, type_).context(here!())?
+                self.visit_type(path, what, chain, type_).context(here!())?
             }
             Type::QualifiedPath {
                 self_type, trait_, ..
             } => {
-                self.visit_type(path, &ErrorLocation::QualifiedSelfType, self_type)?;
+                let self_chain = nested(chain, TypePosition::QualifiedSelfType);
+                self.visit_type(path, what, &self_chain, self_type)?;
                 if let Some(trait_) = trait_ {
-                    self.check_rustdoc_path(
-                        path,
-                        &ErrorLocation::QualifiedSelfTypeAsTrait,
-                        trait_,
-                    )?;
+                    let trait_chain = nested(chain, TypePosition::QualifiedTrait);
+                    self.check_rustdoc_path(path, what, &trait_chain, trait_)?;
                 }
             }
         }
         Ok(())
     }
 
-    #[instrument(level = "debug", skip(self, path, args), fields(path = %path))]
-    fn visit_generic_args(&self, path: &Path, args: &GenericArgs) -> Result<()> {
+    /// Visits the generic args of the type or trait named `of`.
+    #[instrument(level = "debug", skip(self, path, chain, args), fields(path = %path))]
+    fn visit_generic_args(
+        &self,
+        path: &Path,
+        what: &ErrorLocation,
+        chain: &[TypePosition],
+        of: &str,
+        args: &GenericArgs,
+    ) -> Result<()> {
         match args {
             GenericArgs::AngleBracketed { args, bindings } => {
-                for arg in args {
+                for (index, arg) in args.iter().enumerate() {
                     match arg {
                         rustdoc_types::GenericArg::Type(typ) => {
-                            self.visit_type(path, &ErrorLocation::GenericArg, typ)?
+                            let of = of.to_string();
+                            let chain = nested(chain, TypePosition::GenericArg { index, of });
+                            self.visit_type(path, what, &chain, typ)?
                         }
                         rustdoc_types::GenericArg::Lifetime(_)
                         | rustdoc_types::GenericArg::Const(_)
@@ -478,26 +742,34 @@ impl Visitor {
                     }
                 }
                 for binding in bindings {
+                    let position = TypePosition::AssocBinding {
+                        name: binding.name.clone(),
+                        of: of.to_string(),
+                    };
+                    let chain = nested(chain, position);
                     match &binding.binding {
                         rustdoc_types::TypeBindingKind::Equality(term) => {
                             if let Term::Type(typ) = term {
-                                self.visit_type(path, &ErrorLocation::GenericDefaultBinding, typ)
-                                    .context(here!())?;
+                                self.visit_type(path, what, &chain, typ).context(here!())?;
                             }
                         }
                         rustdoc_types::TypeBindingKind::Constraint(bounds) => {
-                            self.visit_generic_bounds(path, bounds)?;
+                            self.visit_generic_bounds(path, what, &chain, bounds)?;
                         }
                     }
                 }
             }
             GenericArgs::Parenthesized { inputs, output } => {
-                for input in inputs {
-                    self.visit_type(path, &ErrorLocation::ClosureInput, input)
+                for (index, input) in inputs.iter().enumerate() {
+                    let of = of.to_string();
+                    let chain = nested(chain, TypePosition::FnInput { index, of });
+                    self.visit_type(path, what, &chain, input)
                         .context(here!())?;
                 }
                 if let Some(output) = output {
-                    self.visit_type(path, &ErrorLocation::ClosureOutput, output)
+                    let of = of.to_string();
+                    let chain = nested(chain, TypePosition::FnOutput { of });
+                    self.visit_type(path, what, &chain, output)
                         .context(here!())?;
                 }
             }
@@ -505,8 +777,14 @@ impl Visitor {
         Ok(())
     }
 
-    #[instrument(level = "debug", skip(self, path, bounds), fields(path = %path))]
-    fn visit_generic_bounds(&self, path: &Path, bounds: &[GenericBound]) -> Result<()> {
+    #[instrument(level = "debug", skip(self, path, chain, bounds), fields(path = %path))]
+    fn visit_generic_bounds(
+        &self,
+        path: &Path,
+        what: &ErrorLocation,
+        chain: &[TypePosition],
+        bounds: &[GenericBound],
+    ) -> Result<()> {
         for bound in bounds {
             if let GenericBound::TraitBound {
                 trait_,
@@ -514,7 +792,7 @@ impl Visitor {
                 ..
             } = bound
             {
-                self.check_rustdoc_path(path, &ErrorLocation::TraitBound, trait_)
+                self.check_rustdoc_path(path, what, chain, trait_)
                     .context(here!())?;
                 self.visit_generic_param_defs(path, generic_params)?;
             }
@@ -531,14 +809,14 @@ impl Visitor {
                     default,
                     synthetic: _,
                 } => {
-                    self.visit_generic_bounds(path, bounds)?;
+                    self.visit_generic_bounds(path, &ErrorLocation::TraitBound, &[], bounds)?;
                     if let Some(typ) = default {
-                        self.visit_type(path, &ErrorLocation::GenericDefaultBinding, typ)
+                        self.visit_type(path, &ErrorLocation::GenericDefaultBinding, &[], typ)
                             .context(here!())?;
                     }
                 }
                 GenericParamDefKind::Const { type_, .. } => {
-                    self.visit_type(path, &ErrorLocation::ConstGeneric, type_)
+                    self.visit_type(path, &ErrorLocation::ConstGeneric, &[], type_)
                         .context(here!())?;
                 }
                 GenericParamDefKind::Lifetime { .. } => {
@@ -559,16 +837,16 @@ impl Visitor {
                     bounds,
                     generic_params,
                 } => {
-                    self.visit_type(path, &ErrorLocation::WhereBound, type_)
+                    self.visit_type(path, &ErrorLocation::WhereBound, &[], type_)
                         .context(here!())?;
-                    self.visit_generic_bounds(path, bounds)?;
+                    self.visit_generic_bounds(path, &ErrorLocation::TraitBound, &[], bounds)?;
                     self.visit_generic_param_defs(path, generic_params)?;
                 }
                 WherePredicate::RegionPredicate { bounds, .. } => {
-                    self.visit_generic_bounds(path, bounds)?;
+                    self.visit_generic_bounds(path, &ErrorLocation::TraitBound, &[], bounds)?;
                 }
                 WherePredicate::EqPredicate { lhs, .. } => {
-                    self.visit_type(path, &ErrorLocation::WhereBound, lhs)
+                    self.visit_type(path, &ErrorLocation::WhereBound, &[], lhs)
                         .context(here!())?;
                 }
             }
@@ -606,31 +884,96 @@ impl Visitor {
         Ok(())
     }
 
-    #[instrument(level = "debug", skip(self, path, rustdoc_path), fields(path = %path))]
+    #[instrument(level = "debug", skip(self, path, chain, rustdoc_path), fields(path = %path))]
     fn check_rustdoc_path(
         &self,
         path: &Path,
         what: &ErrorLocation,
+        chain: &[TypePosition],
         rustdoc_path: &RustDocPath,
     ) -> Result<()> {
-        self.check_external(path, what, &rustdoc_path.id)
-            .context(here!())?;
+        self.found(
+            path,
+            Finding::TypeRef {
+                what: what.clone(),
+                chain: chain.to_vec(),
+                id: rustdoc_path.id.clone(),
+                written_path: rustdoc_path.name.clone(),
+            },
+        )
+        .context(here!())?;
         if let Some(generic_args) = &rustdoc_path.args {
-            self.visit_generic_args(path, generic_args.as_ref())
+            self.visit_generic_args(path, what, chain, &rustdoc_path.name, generic_args)
                 .context(here!())?;
         }
         Ok(())
     }
 
-    fn check_external(&self, path: &Path, what: &ErrorLocation, id: &Id) -> Result<()> {
+    /// Records a finding at the given path for the items that are being visited, and then
+    /// acts on it.
+    fn found(&self, path: &Path, finding: Finding) -> Result<()> {
+        for recording in self.recordings.borrow_mut().iter_mut() {
+            recording.findings.push(RelativeFinding {
+                path: path.suffix(recording.depth),
+                finding: finding.clone(),
+            });
+        }
+        match finding {
+            Finding::TypeRef {
+                what,
+                chain,
+                id,
+                written_path,
+            } => self.check_external(path, &what, &chain, &id, &written_path)?,
+            Finding::FieldsStripped => self.add_error(ValidationError::fields_stripped(path)),
+            Finding::HiddenModule {
+                name,
+                hidden_module,
+            } => self.add_error(ValidationError::hidden_module(
+                name,
+                &ErrorLocation::ReExport,
+                path.to_string(),
+                path.last_span(),
+                hidden_module,
+            )),
+        }
+        Ok(())
+    }
+
+    fn check_external(
+        &self,
+        path: &Path,
+        what: &ErrorLocation,
+        chain: &[TypePosition],
+        id: &Id,
+        written_path: &str,
+    ) -> Result<()> {
         if let Ok(type_name) = self.type_name(id) {
+            let crate_id = self.item_summary(id).expect("type_name succeeded").crate_id;
+            if crate_id != self.root_crate_id {
+                self.record_external_crate_ref(crate_id, &type_name, written_path, path, what);
+            }
             if !self.config.allows_type(&self.root_crate_name, &type_name) {
-                self.add_error(ValidationError::unapproved_external_type_ref(
+                let key = path
+                    .item_key()
+                    .map(|item_key| format!("{item_key}:{type_name}:{what}:{chain:?}"));
+                let error = ValidationError::unapproved_external_type_ref(
                     type_name,
                     what,
+                    chain,
                     path.to_string(),
                     path.last_span(),
-                ));
+                )
+                .with_top_level_module(path.top_level_module().map(str::to_string));
+                match key {
+                    Some(key) => self
+                        .unapproved_refs
+                        .borrow_mut()
+                        .entry(key)
+                        .or_default()
+                        .push((path.to_string(), error)),
+                    None => self.add_error(error),
+                }
             }
         }
         // Crates like `pin_project` do some shenanigans to create and reference types that don't end up
@@ -641,6 +984,47 @@ impl Visitor {
         Ok(())
     }
 
+    /// Remembers the first reference to each external crate ID, and the names that the crate
+    /// is written with, regardless of whether the referenced type is allowed by the config.
+    fn record_external_crate_ref(
+        &self,
+        crate_id: u32,
+        type_name: &str,
+        written_path: &str,
+        path: &Path,
+        what: &ErrorLocation,
+    ) {
+        let crate_name = type_name.split("::").next().unwrap_or(type_name);
+        let sort_key = format!("{}:{type_name}", location_sort_key(path.last_span()));
+        let usage = || CrateUsage {
+            type_name: type_name.into(),
+            version: None,
+            what: what.clone(),
+            in_what_type: path.to_string(),
+            location: path.last_span().cloned(),
+        };
+        let mut refs = self.external_crate_refs.borrow_mut();
+        let crate_ref = refs
+            .entry(crate_name.to_string())
+            .or_default()
+            .entry(crate_id)
+            .or_insert_with(|| ExternalCrateRef {
+                sort_key: sort_key.clone(),
+                usage: usage(),
+                written_crate_names: BTreeSet::new(),
+            });
+        if crate_ref.sort_key > sort_key {
+            crate_ref.sort_key = sort_key;
+            crate_ref.usage = usage();
+        }
+        // Only paths with more than one segment start with a crate name
+        if let Some((written_crate_name, _)) = written_path.split_once("::") {
+            crate_ref
+                .written_crate_names
+                .insert(written_crate_name.to_string());
+        }
+    }
+
     fn add_error(&self, error: ValidationError) {
         debug!("detected error {:?}", error);
         self.errors.borrow_mut().add(error);
@@ -693,6 +1077,13 @@ impl Visitor {
     }
 }
 
+/// Returns the given chain of type positions with one more position at the end.
+fn nested(chain: &[TypePosition], position: TypePosition) -> Vec<TypePosition> {
+    let mut nested = chain.to_vec();
+    nested.push(position);
+    nested
+}
+
 /// Check each segment of a module path against the index. If a segment isn't present in the index,
 /// assume that it's the hidden module and return it. Because the path
 fn infer_first_hidden_module_in_import_source(
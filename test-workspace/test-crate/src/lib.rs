@@ -157,3 +157,11 @@ impl AssocConstStruct {
 
     pub const OTHER_CONST: SimpleNewType = SimpleNewType(5);
 }
+
+pub fn external_in_nested_generics() -> Option<Result<(), SomeStruct>> {
+    unimplemented!()
+}
+
+pub fn external_in_tuple(_tuple: (u8, &[SomeStruct])) {}
+
+pub fn external_in_fn_pointer(_callback: fn(u8) -> Vec<SomeOtherStruct>) {}
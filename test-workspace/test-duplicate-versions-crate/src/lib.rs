@@ -0,0 +1,17 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+#![no_std]
+
+//! This crate exposes the same type from two different versions of `versioned-lib`, one of which
+//! it only depends on through `versioned-lib-wrapper`
+
+pub fn old_request() -> versioned_lib_v1::Request {
+    versioned_lib_v1::Request
+}
+
+pub fn new_request() -> versioned_lib_wrapper::Request {
+    versioned_lib_wrapper::Request
+}
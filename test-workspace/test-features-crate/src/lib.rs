@@ -0,0 +1,27 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+#![no_std]
+
+//! This crate exposes different external types depending on which features are enabled
+
+pub fn always_exposed() -> external_lib::SomeStruct {
+    external_lib::SomeStruct
+}
+
+#[cfg(feature = "simple")]
+pub fn exposed_with_simple() -> external_lib::SimpleNewType {
+    external_lib::SimpleNewType(0)
+}
+
+#[cfg(feature = "other")]
+pub fn exposed_with_other() -> external_lib::SomeOtherStruct {
+    external_lib::SomeOtherStruct
+}
+
+#[cfg(docsrs)]
+pub fn exposed_with_docsrs() -> external_lib::ReprCType {
+    loop {}
+}
@@ -0,0 +1,10 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+pub fn exposed_by_example() -> external_lib::SimpleNewType {
+    external_lib::SimpleNewType(0)
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+//! This crate has a `[lib] name` that differs from its package name, as well as a binary
+//! and an example, so cargo can only document it if a single target is selected.
+
+pub struct LibStruct;
+
+pub fn exposed_by_lib() -> external_lib::SomeStruct {
+    external_lib::SomeStruct
+}
@@ -0,0 +1,15 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+// Types from the package's own library aren't external to its binary
+pub fn uses_own_lib() -> renamed_lib::LibStruct {
+    renamed_lib::LibStruct
+}
+
+pub fn exposed_by_bin() -> external_lib::SomeOtherStruct {
+    external_lib::SomeOtherStruct
+}
+
+fn main() {}
@@ -14,3 +14,33 @@ pub mod something {
 
 pub use external_lib::SomeOtherStruct;
 pub use external_lib::SomeStruct;
+
+mod private {
+    pub struct Shared {
+        pub field: external_lib::SomeStruct,
+    }
+}
+
+// The same item is reachable through more than one path
+pub use private::Shared;
+pub mod again {
+    pub use crate::private::Shared;
+}
+
+// Modules that re-export each other
+pub mod cycle_a {
+    pub use crate::cycle_b;
+}
+pub mod cycle_b {
+    pub use crate::cycle_a;
+}
+
+// Items are named by their shortest public path, even when it isn't where they're declared
+pub mod nested {
+    pub mod deeper {
+        pub struct Deep {
+            pub field: external_lib::SomeStruct,
+        }
+    }
+}
+pub use nested::deeper::Deep;
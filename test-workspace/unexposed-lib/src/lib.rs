@@ -0,0 +1,10 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+#![no_std]
+
+//! This crate is a dependency of `test-crate` that never appears in its public API
+
+pub struct NeverExposed;
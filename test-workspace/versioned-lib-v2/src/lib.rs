@@ -0,0 +1,10 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+#![no_std]
+
+//! Two versions of this crate are used by `test-duplicate-versions-crate`
+
+pub struct Request;
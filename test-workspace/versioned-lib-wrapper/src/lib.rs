@@ -0,0 +1,11 @@
+/*
+ * Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+ * SPDX-License-Identifier: Apache-2.0
+ */
+
+#![no_std]
+
+//! Re-exports a type from `versioned-lib` so that `test-duplicate-versions-crate` only depends on
+//! that version indirectly
+
+pub use versioned_lib::Request;
@@ -4,10 +4,14 @@
  */
 
 use cargo_check_external_types::cargo::handle_failure;
+use cargo_check_external_types::config::Config;
+use cargo_check_external_types::report::MarkdownTable;
+use cargo_check_external_types::rustdoc_json::read_crate;
+use cargo_check_external_types::visitor::Visitor;
 use pretty_assertions::assert_str_eq;
 use std::fs;
 use std::path::Path;
-use std::process::Output;
+use std::process::{Command, Output};
 use test_bin::get_test_bin;
 
 /// Returns (stdout, stderr)
@@ -43,6 +47,49 @@ fn with_default_config() {
     assert_str_eq!(expected_output, actual_output);
 }
 
+#[test]
+fn with_group_by_type() {
+    let expected_output = fs::read_to_string("tests/group-by-type-expected-output.md").unwrap();
+    let actual_output = run_with_args("test-workspace/test-crate", &["--group-by-type"]);
+    assert_str_eq!(expected_output, actual_output);
+}
+
+#[test]
+fn with_message_stream_and_color() {
+    let run = |args: &[&str]| {
+        let output = get_test_bin("cargo-check-external-types")
+            .current_dir("test-workspace/test-crate")
+            .arg("check-external-types")
+            .args(args)
+            .output()
+            .expect("failed to start cargo-check-external-types");
+        assert_eq!(Some(1), output.status.code());
+        output_text(&output)
+    };
+    let expected_output = fs::read_to_string("tests/default-config-expected-output.md").unwrap();
+
+    let (stdout, stderr) = run(&["--message-stream", "stderr"]);
+    assert_str_eq!("", stdout);
+    assert!(stderr.ends_with(&expected_output), "{stderr}");
+
+    let (stdout, _) = run(&["--color", "always"]);
+    assert!(stdout.contains("\u{1b}["), "{stdout}");
+}
+
+#[test]
+fn test_explain() {
+    let output = get_test_bin("cargo-check-external-types")
+        .args(["check-external-types", "--explain", "CET0001"])
+        .output()
+        .expect("failed to start cargo-check-external-types");
+    handle_failure("cargo-check-external-types", &output).unwrap();
+    let (stdout, _) = output_text(&output);
+    assert!(
+        stdout.starts_with("CET0001: An external type is referenced in the public API"),
+        "{stdout}"
+    );
+}
+
 #[test]
 fn with_some_allowed_types() {
     let expected_output = fs::read_to_string("tests/allow-some-types-expected-output.md").unwrap();
@@ -53,6 +100,18 @@ fn with_some_allowed_types() {
     assert_str_eq!(expected_output, actual_output);
 }
 
+// Globs in the config that almost match an unapproved type are suggested as the likely culprit
+#[test]
+fn with_misspelled_allowed_type() {
+    let expected_output =
+        fs::read_to_string("tests/misspelled-allowed-type-expected-output.md").unwrap();
+    let actual_output = run_with_args(
+        "test-workspace/test-lib-name-crate",
+        &["--config", "../../tests/allow-misspelled-types.toml"],
+    );
+    assert_str_eq!(expected_output, actual_output);
+}
+
 #[test]
 fn with_output_format_markdown_table() {
     let expected_output =
@@ -64,6 +123,56 @@ fn with_output_format_markdown_table() {
     assert_str_eq!(expected_output, actual_output);
 }
 
+// Warnings are listed along with the errors, so that the table doesn't hide that some items
+// couldn't be checked
+#[test]
+fn with_output_format_markdown_table_grouped_by_crate() {
+    let expected_output =
+        fs::read_to_string("tests/output-format-markdown-table-grouped-expected-output.md")
+            .unwrap();
+    let actual_output = run_with_args(
+        "test-workspace/test-type-exported-from-hidden-module",
+        &["--output-format", "markdown-table", "--group-by-crate"],
+    );
+    assert_str_eq!(expected_output, actual_output);
+}
+
+#[test]
+fn with_output_format_html() {
+    let expected_output =
+        fs::read_to_string("tests/output-format-html-expected-output.html").unwrap();
+    let actual_output = run_with_args(
+        "test-workspace/test-type-exported-from-hidden-module",
+        &["--output-format", "html"],
+    );
+    assert_str_eq!(expected_output, actual_output);
+
+    // The summary is part of the page rather than following it
+    let expected_output =
+        fs::read_to_string("tests/output-format-html-summary-expected-output.html").unwrap();
+    let actual_output = run_with_args(
+        "test-workspace/test-type-exported-from-hidden-module",
+        &["--output-format", "html", "--summary"],
+    );
+    assert_str_eq!(expected_output, actual_output);
+}
+
+#[test]
+fn with_output_format_summary() {
+    let expected_output =
+        fs::read_to_string("tests/output-format-summary-expected-output.md").unwrap();
+    let actual_output = run_with_args("test-workspace/test-crate", &["--output-format", "summary"]);
+    assert_str_eq!(expected_output, actual_output);
+
+    // The summary can also follow the errors
+    let default_output = fs::read_to_string("tests/default-config-expected-output.md").unwrap();
+    let actual_output = run_with_args("test-workspace/test-crate", &["--summary"]);
+    assert_str_eq!(
+        format!("{default_output}\n{expected_output}"),
+        actual_output
+    );
+}
+
 // Make sure that the visitor doesn't attempt to visit the inner items of re-exported external types.
 // Rustdoc doesn't include these inner items in its JSON output, which leads to obtuse crashes if they're
 // referenced. It's also just the wrong behavior to look into the type being re-exported, since if it's
@@ -83,3 +192,237 @@ fn test_type_exported_from_hidden_module() {
     let actual_output = run_with_args("test-workspace/test-type-exported-from-hidden-module", &[]);
     assert_str_eq!(expected_output, actual_output);
 }
+
+#[test]
+fn with_output_format_dependency_report() {
+    let expected_output =
+        fs::read_to_string("tests/output-format-dependency-report-expected-output.md").unwrap();
+    let actual_output = run_with_args(
+        "test-workspace/test-crate",
+        &[
+            "--config",
+            "../../tests/allow-some-types.toml",
+            "--output-format",
+            "dependency-report",
+        ],
+    );
+    assert_str_eq!(expected_output, actual_output);
+}
+
+// Output in rustdoc JSON format version 57 should give the same results as the format version
+// that the visitor works with. The fixture is the unmodified output of a nightly rustdoc for
+// `test-workspace/test-crate`, generated with:
+//
+//   cargo +nightly rustdoc -- --document-private-items -Z unstable-options --output-format json
+//
+// and should be regenerated the same way when the test crate changes.
+#[test]
+fn test_rustdoc_json_format_57() {
+    let package = read_crate(Path::new("tests/test-crate-rustdoc-json-v57.json")).unwrap();
+    let errors = Visitor::new(Config::default(), package)
+        .unwrap()
+        .visit_all()
+        .unwrap();
+    let expected_output =
+        fs::read_to_string("tests/output-format-markdown-table-expected-output.md").unwrap();
+    assert_str_eq!(expected_output, MarkdownTable::new(&errors).to_string());
+}
+
+// Types from two versions of the same crate should be reported even when the crate is allowed,
+// since users of the API can't pass one version's type where the other is expected.
+#[test]
+fn test_duplicate_crate_versions() {
+    let expected_output =
+        fs::read_to_string("tests/test-duplicate-versions-expected-output.md").unwrap();
+    let actual_output = run_with_args(
+        "test-workspace/test-duplicate-versions-crate",
+        &["--config", "../../tests/allow-versioned-lib.toml"],
+    );
+    assert_str_eq!(expected_output, actual_output);
+}
+
+#[test]
+fn test_each_feature() {
+    let expected_output = fs::read_to_string("tests/test-each-feature-expected-output.md").unwrap();
+    let actual_output = run_with_args("test-workspace/test-features-crate", &["--each-feature"]);
+    assert_str_eq!(expected_output, actual_output);
+}
+
+// Uses a separate target directory since runs are only kept from overwriting each other's
+// rustdoc output within a single process
+#[test]
+fn test_each_feature_in_parallel() {
+    let expected_output = fs::read_to_string("tests/test-each-feature-expected-output.md").unwrap();
+    let actual_output = run_with_args(
+        "test-workspace/test-features-crate",
+        &[
+            "--each-feature",
+            "--jobs",
+            "4",
+            "--target-dir",
+            "../target/parallel",
+        ],
+    );
+    assert_str_eq!(expected_output, actual_output);
+}
+
+// Uses a separate target directory so that the `--cfg docsrs` build doesn't invalidate the
+// rustdoc output of the other tests.
+#[test]
+fn test_cargo_and_rustdoc_args() {
+    let expected_output =
+        fs::read_to_string("tests/test-cargo-and-rustdoc-args-expected-output.md").unwrap();
+    let actual_output = run_with_args(
+        "test-workspace/test-features-crate",
+        &[
+            "--offline",
+            "--target-dir",
+            "../target/rustdoc-args",
+            "--",
+            "--cfg",
+            "docsrs",
+        ],
+    );
+    assert_str_eq!(expected_output, actual_output);
+}
+
+// Setting `RUSTC_BOOTSTRAP` changes cargo's fingerprint, so a separate target directory is
+// used to avoid rebuilding the other tests' rustdoc output.
+#[test]
+fn test_allow_bootstrap() {
+    let expected_output = fs::read_to_string("tests/default-config-expected-output.md").unwrap();
+    let actual_output = run_with_args(
+        "test-workspace/test-crate",
+        &[
+            "--allow-bootstrap",
+            "--target-dir",
+            "../target/allow-bootstrap",
+        ],
+    );
+    assert_str_eq!(expected_output, actual_output);
+}
+
+#[test]
+fn test_reuse_rustdoc_output() {
+    let run = |args: &[&str]| {
+        let mut cmd = get_test_bin("cargo-check-external-types");
+        cmd.current_dir("test-workspace/test-crate")
+            .arg("check-external-types")
+            .args(["--target-dir", "../target/reuse-output"])
+            .args(args);
+        let output = cmd
+            .output()
+            .expect("failed to start cargo-check-external-types");
+        output_text(&output)
+    };
+    let reused = "Reusing rustdoc JSON output";
+    let expected_output = fs::read_to_string("tests/default-config-expected-output.md").unwrap();
+
+    // Running without the cache also removes any fingerprint left over from a previous test run
+    let (stdout, stderr) = run(&["--no-cache"]);
+    assert_str_eq!(expected_output, stdout);
+    assert!(!stderr.contains(reused), "{stderr}");
+
+    let (stdout, stderr) = run(&[]);
+    assert_str_eq!(expected_output, stdout);
+    assert!(!stderr.contains(reused), "{stderr}");
+
+    let (stdout, stderr) = run(&[]);
+    assert_str_eq!(expected_output, stdout);
+    assert!(stderr.contains(reused), "{stderr}");
+
+    // Changing an option that's passed to rustdoc invalidates the output
+    let (_, stderr) = run(&["--", "--cfg", "docsrs"]);
+    assert!(!stderr.contains(reused), "{stderr}");
+}
+
+// The test workspace pins the toolchain that matches the supported format version, so both
+// naming that toolchain and searching for it should give the same output as the default.
+#[test]
+fn test_toolchain() {
+    let toolchain = fs::read_to_string("rust-toolchain.toml")
+        .unwrap()
+        .lines()
+        .find_map(|line| line.strip_prefix("channel = "))
+        .expect("rust-toolchain.toml has a channel")
+        .trim_matches('"')
+        .to_string();
+
+    let expected_output = fs::read_to_string("tests/default-config-expected-output.md").unwrap();
+    let actual_output = run_with_args("test-workspace/test-crate", &["--toolchain", &toolchain]);
+    assert_str_eq!(expected_output, actual_output);
+    let actual_output = run_with_args("test-workspace/test-crate", &["--toolchain", "auto"]);
+    assert_str_eq!(expected_output, actual_output);
+}
+
+// The doctor's output has paths and toolchain versions that depend on the environment,
+// so only check the parts that don't.
+#[test]
+fn test_doctor() {
+    // A separate target directory keeps other tests from writing the JSON output while it's read
+    let output = run_with_args(
+        "test-workspace/test-features-crate",
+        &[
+            "doctor",
+            "--config",
+            "../../tests/allow-some-types.toml",
+            "--target-dir",
+            "../target/doctor",
+        ],
+    );
+    let lines: Vec<&str> = output.lines().collect();
+    assert!(lines.iter().all(|line| line.starts_with("ok:")), "{output}");
+    assert!(
+        lines.contains(&"ok:      features: default, simple"),
+        "{output}"
+    );
+    assert!(
+        lines.contains(
+            &"ok:      config file: ../../tests/allow-some-types.toml (1 allowed external type patterns)"
+        ),
+        "{output}"
+    );
+    assert!(
+        lines.iter().any(|line| line.contains("JSON output path: ")
+            && line.ends_with(
+                "check-external-types/doc/test_features_crate.json (not generated yet)"
+            )),
+        "{output}"
+    );
+}
+
+// Checking for an explicit target reads the rustdoc output from a different directory,
+// so use the host target to test that without needing any other targets installed.
+#[test]
+fn test_explicit_target() {
+    let output = Command::new("rustc").arg("-vV").output().unwrap();
+    handle_failure("rustc -vV", &output).unwrap();
+    let (stdout, _) = output_text(&output);
+    let host = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("host: "))
+        .expect("rustc -vV output includes the host");
+
+    let expected_output = run_with_args("test-workspace/test-features-crate", &[]);
+    let actual_output = run_with_args("test-workspace/test-features-crate", &["--target", host]);
+    assert_str_eq!(expected_output, actual_output);
+}
+
+// The package has a library with a `[lib] name`, a binary, and an example, so the library
+// must be selected explicitly and its JSON output is named after the lib name.
+#[test]
+fn test_lib_name() {
+    let expected_output = fs::read_to_string("tests/test-lib-name-expected-output.md").unwrap();
+    let actual_output = run_with_args("test-workspace/test-lib-name-crate", &[]);
+    assert_str_eq!(expected_output, actual_output);
+}
+
+#[test]
+fn test_bin() {
+    let expected_output = fs::read_to_string("tests/test-lib-name-bin-expected-output.md").unwrap();
+    let actual_output = run_with_args(
+        "test-workspace/test-lib-name-crate",
+        &["--bin", "test-lib-name-crate"],
+    );
+    assert_str_eq!(expected_output, actual_output);
+}